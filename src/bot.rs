@@ -0,0 +1,126 @@
+//! This module contains what is common to all the bots, whether they are built in (see `mcts`)
+//! or run as external processes (see `external`).
+//!
+//! Bots are described by a specification string, used on the command line:
+//!
+//! - `mcts`: the built-in Monte Carlo tree search bot, searching for the whole time budget
+//! - `mcts:N`: the same bot, running exactly `N` iterations per turn, which makes it
+//!   independent of the speed of the machine
+//! - anything else is the command of an external bot
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use futures::sync::mpsc::channel;
+use futures::Stream;
+
+use connection::ConnectionProxy;
+use core::{StampedAction, Update};
+use external::ExternalBot;
+use mcts::{Budget, MctsBot};
+
+/// A player that is not a human.
+pub trait Bot: Send {
+    /// Return the action the bot wants to perform, given the last update of the game, filtered
+    /// for the bot's player. Like the actions of the websocket clients, it can tell which turn it
+    /// is based on and carry a sequence number. An error means the bot cannot play anymore, and is
+    /// treated as a resignation.
+    fn play(&mut self, update: &Update) -> Result<Option<StampedAction>, BotError>;
+}
+
+/// Return the bot described by the given specification (see the module documentation). `budget`
+/// is the time the bot has to answer each update.
+pub fn from_spec(spec: &str, budget: Duration) -> Result<Box<dyn Bot>, BotError> {
+    let mut parts = spec.splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some("mcts"), None) => Ok(Box::new(MctsBot::new(Budget::Time(budget)))),
+        (Some("mcts"), Some(iterations)) => match iterations.parse() {
+            Ok(iterations) => Ok(Box::new(MctsBot::new(Budget::Iterations(iterations)))),
+            Err(_) => Err(BotError::InvalidSpec(spec.to_string())),
+        },
+        _ => Ok(Box::new(ExternalBot::spawn(spec, budget)?)),
+    }
+}
+
+/// Start the bot described by the given specification, and return a `ConnectionProxy` that can
+/// be used to add the bot to a game, just like a websocket client.
+///
+/// The bot is driven by a dedicated thread. When the bot fails, the thread drops its end of the
+/// actions channel, which the game treats as a resignation.
+pub fn spawn_proxy(spec: &str, budget: Duration) -> Result<ConnectionProxy, BotError> {
+    let mut bot = from_spec(spec, budget)?;
+    let spec = spec.to_string();
+    let (mut action_tx, action_rx) = channel(10);
+    let (update_tx, update_rx) = channel(10);
+
+    thread::spawn(move || {
+        // `wait()` turns the stream into a blocking iterator, which ends when the game drops its
+        // end of the channel.
+        for update in update_rx.wait() {
+            let update = match update {
+                Ok(update) => update,
+                Err(()) => break,
+            };
+            match bot.play(&update) {
+                Ok(Some(action)) => {
+                    // If the channel is full already, discard the action
+                    if action_tx.try_send(action).is_err() {
+                        error!("Discarding action from bot \"{}\"", spec);
+                    }
+                }
+                Ok(None) => {}
+                Err(_) => break,
+            }
+        }
+    });
+
+    Ok(ConnectionProxy::new(action_rx, update_tx))
+}
+
+#[derive(Debug)]
+pub enum BotError {
+    /// The bot did not answer within its time budget.
+    Timeout,
+    /// The bot exited, or closed its stdout.
+    Exited,
+    /// The bot specification is not valid.
+    InvalidSpec(String),
+    Io(io::Error),
+}
+
+impl From<io::Error> for BotError {
+    fn from(err: io::Error) -> Self {
+        BotError::Io(err)
+    }
+}
+
+impl Error for BotError {
+    fn description(&self) -> &str {
+        match *self {
+            BotError::Timeout => "the bot did not answer in time",
+            BotError::Exited => "the bot exited",
+            BotError::InvalidSpec(_) => "invalid bot specification",
+            BotError::Io(ref e) => e.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        if let BotError::Io(ref e) = *self {
+            Some(e)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BotError::Io(e) => write!(f, "Bot error (io): {}", e),
+            BotError::InvalidSpec(spec) => write!(f, "Bot error: {}: {}", self.description(), spec),
+            _ => write!(f, "Bot error: {}", self.description()),
+        }
+    }
+}
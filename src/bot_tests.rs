@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use bot::{from_spec, BotError};
+use core::Game;
+
+const BUDGET: Duration = Duration::from_millis(100);
+
+#[test]
+fn test_from_spec() {
+    let update = Game::with_seed(vec![0, 1], 0)
+        .unwrap()
+        .get_update()
+        .filtered(0);
+    for spec in &["mcts", "mcts:10"] {
+        let mut bot = from_spec(spec, BUDGET).unwrap();
+        assert!(bot.play(&update).is_ok());
+    }
+
+    // `cat` answers with the update, which is not a valid action: it's ignored.
+    let mut bot = from_spec("cat", BUDGET).unwrap();
+    assert!(bot.play(&update).unwrap().is_none());
+
+    for spec in &["mcts:abc", "mcts:", "mcts:-1"] {
+        match from_spec(spec, BUDGET) {
+            Err(BotError::InvalidSpec(ref s)) if s == spec => {}
+            Err(e) => panic!("unexpected error for {:?}: {}", spec, e),
+            Ok(_) => panic!("{:?} is not a valid specification", spec),
+        }
+    }
+    for spec in &["", "./no-such-bot"] {
+        match from_spec(spec, BUDGET) {
+            Err(BotError::Io(_)) => {}
+            Err(e) => panic!("unexpected error for {:?}: {}", spec, e),
+            Ok(_) => panic!("{:?} is not a valid command", spec),
+        }
+    }
+}
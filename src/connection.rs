@@ -1,4 +1,3 @@
-use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt;
 
@@ -12,13 +11,13 @@ use tokio_tungstenite::WebSocketStream;
 use tungstenite::Error as WebSocketError;
 use tungstenite::Message;
 
-use core::{Action, Move, Update};
+use core::{Action, Game, Move, MoveQueue, PlayerId, StampedAction, Update};
 
 use serde_json;
 
 pub struct Connection<S> {
     ws: WebSocketStream<S>,
-    actions: Sender<Action>,
+    actions: Sender<StampedAction>,
     updates: Receiver<Update>,
 }
 
@@ -60,13 +59,7 @@ where
             actions: action_tx,
             updates: update_rx,
         };
-        let proxy = ConnectionProxy {
-            actions: action_rx,
-            updates: update_tx,
-            pending_moves: VecDeque::new(),
-            resigned: false,
-        };
-        (connection, proxy)
+        (connection, ConnectionProxy::new(action_rx, update_tx))
     }
     /// Start processing messages from the client
     fn process_new_messages(&mut self) -> Poll<(), ConnectionError> {
@@ -121,26 +114,50 @@ where
     }
 }
 
+/// Default maximum number of moves a player can queue
+pub const DEFAULT_MAX_QUEUE_LEN: usize = 100;
+
+/// Default number of turns after which moves are considered stale
+pub const DEFAULT_STALE_WINDOW: usize = 2;
+
 pub struct ConnectionProxy {
-    pub actions: Receiver<Action>,
+    pub actions: Receiver<StampedAction>,
     pub updates: Sender<Update>,
-    pub pending_moves: VecDeque<Move>,
+    pub pending_moves: MoveQueue,
+    /// Maximum number of moves the player can queue. Moves queued beyond that are dropped.
+    pub max_queue_len: usize,
+    /// Actions that queue moves are discarded if they are based on a turn that is older than the
+    /// current turn by more than this number of turns.
+    pub stale_window: usize,
+    /// Sequence number of the last action processed for the player
+    pub last_seq: Option<u64>,
     pub resigned: bool,
 }
 
 impl ConnectionProxy {
-    pub fn poll_actions(&mut self) {
+    pub fn new(actions: Receiver<StampedAction>, updates: Sender<Update>) -> Self {
+        ConnectionProxy {
+            actions,
+            updates,
+            pending_moves: MoveQueue::new(),
+            max_queue_len: DEFAULT_MAX_QUEUE_LEN,
+            stale_window: DEFAULT_STALE_WINDOW,
+            last_seq: None,
+            resigned: false,
+        }
+    }
+
+    /// Process the actions received from the player. The game is used to plan the paths of
+    /// `MoveTo` actions. If the queue of the player gets longer than `max_queue_len`, the moves
+    /// at the end of the queue are dropped.
+    pub fn poll_actions(&mut self, game: &Game, player: PlayerId) {
         loop {
-            let ConnectionProxy {
-                ref mut actions,
-                ref mut pending_moves,
-                ..
-            } = *self;
-
-            match actions.poll() {
-                Ok(Async::Ready(Some(Action::CancelMoves))) => pending_moves.truncate(0),
-                Ok(Async::Ready(Some(Action::Resign))) => break,
-                Ok(Async::Ready(Some(Action::Move(mv)))) => pending_moves.push_back(mv),
+            match self.actions.poll() {
+                Ok(Async::Ready(Some(StampedAction {
+                    action: Action::Resign,
+                    ..
+                }))) => break,
+                Ok(Async::Ready(Some(stamped))) => self.process_action(game, player, stamped),
                 Ok(Async::NotReady) => return,
                 Ok(Async::Ready(None)) => {
                     warn!("remote end of actions channel closed");
@@ -160,13 +177,32 @@ impl ConnectionProxy {
         self.resign();
     }
 
+    /// Process an action that is not a resignation.
+    pub(crate) fn process_action(&mut self, game: &Game, player: PlayerId, stamped: StampedAction) {
+        if stamped.seq.is_some() {
+            self.last_seq = stamped.seq;
+        }
+        queue_action(
+            &mut self.pending_moves,
+            game,
+            player,
+            &stamped,
+            self.stale_window,
+            self.max_queue_len,
+        );
+    }
+
     pub fn resign(&mut self) {
         self.resigned = true;
-        self.pending_moves.truncate(0);
+        self.pending_moves.clear();
     }
 
-    pub fn get_move(&mut self) -> Option<Move> {
-        self.pending_moves.pop_front()
+    /// Take the next move of the player from its queue. See `MoveQueue::pop`.
+    pub fn get_move(&mut self, game: &Game, player: PlayerId) -> Option<Move> {
+        let mv = self.pending_moves.pop(game, player);
+        // Planning a path again can make the queue longer.
+        self.pending_moves.truncate(self.max_queue_len);
+        mv
     }
 
     pub fn has_resigned(&self) -> bool {
@@ -174,6 +210,38 @@ impl ConnectionProxy {
     }
 }
 
+/// Apply an action that is not a resignation to the given queue of moves, unless it queues moves
+/// and is based on a turn that is older than the current turn by more than `stale_window` turns.
+/// If the queue gets longer than `max_queue_len`, the moves at the end of the queue are dropped.
+pub(crate) fn queue_action(
+    pending_moves: &mut MoveQueue,
+    game: &Game,
+    player: PlayerId,
+    stamped: &StampedAction,
+    stale_window: usize,
+    max_queue_len: usize,
+) {
+    let action = &stamped.action;
+    if let Some(turn) = stamped.turn {
+        if action.queues_moves() && game.turn().saturating_sub(turn) > stale_window {
+            warn!(
+                "discarding stale action of player {} from turn {} at turn {}",
+                player,
+                turn,
+                game.turn()
+            );
+            return;
+        }
+    }
+    if !pending_moves.apply(game, player, action) {
+        warn!("could not queue {:?} for player {}", action, player);
+    }
+    if pending_moves.len() > max_queue_len {
+        warn!("queue of player {} is full, dropping moves", player);
+        pending_moves.truncate(max_queue_len);
+    }
+}
+
 #[derive(Debug)]
 pub enum ConnectionError {
     WebSocket(WebSocketError),
@@ -0,0 +1,99 @@
+use futures::sync::mpsc::channel;
+
+use connection::ConnectionProxy;
+use core::{Action, Game, Move, StampedAction};
+
+/// Return a game at turn 10, and a connection for its first player.
+fn get_game() -> (Game, ConnectionProxy) {
+    let mut game = Game::with_seed(vec![0, 1], 0).unwrap();
+    for _ in 0..10 {
+        game.incr_turn();
+    }
+    let (_, actions) = channel(10);
+    let (updates, _) = channel(10);
+    let mut connection = ConnectionProxy::new(actions, updates);
+    connection.stale_window = 2;
+    (game, connection)
+}
+
+fn stamped(mv: Move, turn: Option<usize>, seq: Option<u64>) -> StampedAction {
+    StampedAction {
+        action: Action::Move(mv),
+        turn,
+        seq,
+    }
+}
+
+#[test]
+fn test_stale_window() {
+    let (game, mut connection) = get_game();
+    let mv = game.legal_moves(0)[0];
+
+    // Exactly at the boundary of the window
+    connection.process_action(&game, 0, stamped(mv, Some(8), None));
+    assert_eq!(connection.pending_moves.len(), 1);
+    // One turn past it
+    connection.process_action(&game, 0, stamped(mv, Some(7), None));
+    assert_eq!(connection.pending_moves.len(), 1);
+    // Actions from the future are not stale, even if the turn does not fit in the window.
+    connection.process_action(&game, 0, stamped(mv, Some(11), None));
+    connection.process_action(&game, 0, stamped(mv, Some(usize::MAX), None));
+    assert_eq!(connection.pending_moves.len(), 3);
+    // Actions without a turn are never stale.
+    connection.process_action(&game, 0, stamped(mv, None, None));
+    assert_eq!(connection.pending_moves.len(), 4);
+
+    // Stale actions that do not queue moves are still applied.
+    let cancel = StampedAction {
+        action: Action::CancelMoves,
+        turn: Some(0),
+        seq: None,
+    };
+    connection.process_action(&game, 0, cancel);
+    assert_eq!(connection.pending_moves.len(), 0);
+}
+
+#[test]
+fn test_ack() {
+    let (game, mut connection) = get_game();
+    let mv = game.legal_moves(0)[0];
+    assert_eq!(connection.last_seq, None);
+    connection.process_action(&game, 0, stamped(mv, Some(10), Some(1)));
+    assert_eq!(connection.last_seq, Some(1));
+    // Actions without a sequence number do not change the acknowledgement.
+    connection.process_action(&game, 0, stamped(mv, Some(10), None));
+    assert_eq!(connection.last_seq, Some(1));
+    // Discarded actions are acknowledged too.
+    connection.process_action(&game, 0, stamped(mv, Some(0), Some(2)));
+    assert_eq!(connection.last_seq, Some(2));
+    assert_eq!(connection.pending_moves.len(), 2);
+}
+
+#[test]
+fn test_max_queue_len() {
+    let (game, mut connection) = get_game();
+    connection.max_queue_len = 2;
+    // Queued moves do not have to be valid yet.
+    let mv = game.legal_moves(0)[0];
+    let moves: Vec<Move> = (0..3).map(|i| Move { from: i, ..mv }).collect();
+    for mv in &moves {
+        connection.process_action(&game, 0, stamped(*mv, None, None));
+    }
+    // The oldest moves are kept.
+    let queued: Vec<usize> = connection
+        .pending_moves
+        .iter()
+        .map(|queued| queued.mv.from)
+        .collect();
+    assert_eq!(queued, vec![0, 1]);
+
+    let replace = StampedAction {
+        action: Action::ReplaceMoves {
+            moves: moves.clone(),
+        },
+        turn: None,
+        seq: None,
+    };
+    connection.process_action(&game, 0, replace);
+    assert_eq!(connection.pending_moves.len(), 2);
+}
@@ -1,7 +1,9 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 pub type PlayerId = usize;
 
+pub type TeamId = usize;
+
 /// Represent a player during a game.
 #[derive(Serialize, Clone, Debug, PartialEq)]
 pub struct Player {
@@ -15,6 +17,10 @@ pub struct Player {
     /// Turn at which the player was defeated, if any
     #[serde(skip_serializing_if = "Option::is_none")]
     pub defeated_at: Option<usize>,
+
+    /// Team of the player, if the game is played in teams
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team: Option<TeamId>,
 }
 
 /// Small helper used by serde to avoid serializing the owned_tile field if the player does not own
@@ -30,6 +36,7 @@ impl Player {
             id,
             owned_tiles: 0,
             defeated_at: None,
+            team: None,
         }
     }
 
@@ -45,8 +52,44 @@ impl Player {
     }
 }
 
+/// The teams of the players of a game. A player is allied with itself, and with the players of
+/// its team. Players that are not in a team have no other ally.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Teams(HashMap<PlayerId, TeamId>);
+
+impl Teams {
+    /// Return the teams of the given players.
+    pub fn new<'a, I: IntoIterator<Item = &'a Player>>(players: I) -> Self {
+        Teams(
+            players
+                .into_iter()
+                .filter_map(|player| player.team.map(|team| (player.id, team)))
+                .collect(),
+        )
+    }
+
+    /// Return whether the given players are allies.
+    pub fn are_allies(&self, player: PlayerId, other: PlayerId) -> bool {
+        player == other
+            || match (self.0.get(&player), self.0.get(&other)) {
+                (Some(team), Some(other_team)) => team == other_team,
+                _ => false,
+            }
+    }
+
+    /// Return whether all the given players are allies, which is the case if there is at most one
+    /// of them.
+    pub fn all_allies<I: IntoIterator<Item = PlayerId>>(&self, players: I) -> bool {
+        let mut players = players.into_iter();
+        match players.next() {
+            Some(first) => players.all(|player| self.are_allies(first, player)),
+            None => true,
+        }
+    }
+}
+
 /// Represent an action a player can perform.
-#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(tag = "type")]
 #[serde(rename_all = "lowercase")]
 pub enum Action {
@@ -57,6 +100,60 @@ pub enum Action {
     CancelMoves,
     /// Make a move from a tile to another
     Move(Move),
+    /// Move the units of a tile to a tile that may be far away. The server queues the moves of
+    /// the shortest path between the two tiles, and re-plans the path if it becomes invalid.
+    #[serde(rename = "move_to")]
+    MoveTo { from: usize, to: usize },
+    /// Remove the last move queued for the player
+    #[serde(rename = "undo_move")]
+    UndoMove,
+    /// Remove all the queued moves that start from the given tile
+    #[serde(rename = "clear_moves_from")]
+    ClearMovesFrom { tile: usize },
+    /// Make a move before all the moves already queued for the player
+    #[serde(rename = "move_first")]
+    MoveFirst(Move),
+    /// Replace all the moves queued for the player by the given moves
+    #[serde(rename = "replace_moves")]
+    ReplaceMoves { moves: Vec<Move> },
+}
+
+impl Action {
+    /// Return whether the action adds moves to the queue of the player.
+    pub fn queues_moves(&self) -> bool {
+        matches!(
+            *self,
+            Action::Move(_)
+                | Action::MoveTo { .. }
+                | Action::MoveFirst(_)
+                | Action::ReplaceMoves { .. }
+        )
+    }
+}
+
+/// An action, as sent by a player. The player can tell which turn the action was decided at, so
+/// that the server can discard moves based on an outdated view of the game, and give the action a
+/// sequence number, that the server acknowledges in the following updates.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StampedAction {
+    #[serde(flatten)]
+    pub action: Action,
+    /// Turn of the update the action is based on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn: Option<usize>,
+    /// Sequence number of the action, chosen by the player
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+}
+
+impl From<Action> for StampedAction {
+    fn from(action: Action) -> Self {
+        StampedAction {
+            action,
+            turn: None,
+            seq: None,
+        }
+    }
 }
 
 /// Represent a move from one tile to another. During a move, units are transfered from one tile to
@@ -81,6 +178,18 @@ pub enum Direction {
     Down,
 }
 
+impl Direction {
+    /// Return all the directions
+    pub fn all() -> [Direction; 4] {
+        [
+            Direction::Right,
+            Direction::Left,
+            Direction::Up,
+            Direction::Down,
+        ]
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 /// Outcome of a move
 pub enum MoveOutcome {
@@ -89,7 +198,7 @@ pub enum MoveOutcome {
     GeneralCaptured(PlayerId),
     /// Outcome when a move resulted in an open tile or a city tile being captured. If the tile
     /// was belonging to a different player than the one making the move, the player's ID is
-    /// specified.
+    /// specified. This is also the outcome of moves to an allied tile, which changes hands.
     TileCaptured(Option<PlayerId>),
     /// Outcome when a move did not result in a tile being captured.
     StatuQuo,
@@ -109,6 +218,107 @@ pub enum TileKind {
     Mountain,
 }
 
+/// The part of a tile's state the game rules depend on: its type, its owner and its number of
+/// units. Contrary to `Tile`, it does not track visibility, which makes it small and cheap to
+/// copy.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TileState {
+    pub kind: TileKind,
+    pub owner: Option<PlayerId>,
+    pub units: u16,
+}
+
+impl TileState {
+    /// Perform a move from a source tile to a destination tile. Units moved to an allied tile
+    /// are added to the tile's units, and the tile changes hands so that the units can keep
+    /// moving, except if it is a general, which stays with its owner.
+    pub fn attack(
+        &mut self,
+        dst: &mut TileState,
+        teams: &Teams,
+    ) -> Result<MoveOutcome, InvalidMove> {
+        if self.kind == TileKind::Mountain {
+            return Err(InvalidMove::FromInvalidTile);
+        }
+        if dst.kind == TileKind::Mountain {
+            return Err(InvalidMove::ToInvalidTile);
+        }
+        if self.units < 2 {
+            return Err(InvalidMove::NotEnoughUnits);
+        }
+        let attacker = self.owner.ok_or(InvalidMove::SourceTileNotOwned)?;
+
+        let outcome = match dst.owner {
+            // The destination tile belongs to an ally
+            Some(ally) if ally != attacker && teams.are_allies(attacker, ally) => {
+                dst.units += self.units - 1;
+                if dst.kind == TileKind::General {
+                    MoveOutcome::StatuQuo
+                } else {
+                    dst.owner = self.owner;
+                    MoveOutcome::TileCaptured(Some(ally))
+                }
+            }
+            // The destination tile belongs to someone else
+            Some(defender) if defender != attacker => {
+                // The defender has more units.
+                if dst.units >= self.units - 1 {
+                    dst.units -= self.units - 1;
+                    MoveOutcome::StatuQuo
+                }
+                // The attacker has more units. Capture the tile.
+                else {
+                    dst.units = self.units - 1 - dst.units;
+                    dst.owner = self.owner;
+                    // We're capturing a general
+                    if dst.kind == TileKind::General {
+                        //  Turn the general into a regular city
+                        dst.kind = TileKind::City;
+                        MoveOutcome::GeneralCaptured(defender)
+                    }
+                    // We're capturing a regular tile
+                    else {
+                        MoveOutcome::TileCaptured(Some(defender))
+                    }
+                }
+            }
+            // The owner is the same for both tiles, just transfer the unit
+            Some(_defender) => {
+                dst.units += self.units - 1;
+                MoveOutcome::StatuQuo
+            }
+            // The destination tile is not owned by anyone.
+            None => {
+                // The destination has more units, we can't capture it
+                if dst.units >= self.units - 1 {
+                    dst.units -= self.units - 1;
+                    MoveOutcome::StatuQuo
+                } else {
+                    dst.units = self.units - 1 - dst.units;
+                    dst.owner = self.owner;
+                    MoveOutcome::TileCaptured(None)
+                }
+            }
+        };
+        // In any case, we always only leave 1 unit in the source tile
+        // TODO: would be nice to support splitting the source tile units before moving.
+        self.units = 1;
+        Ok(outcome)
+    }
+
+    /// Return whether the tile must be reinforced. If `reinforce_all_tiles` is `false`, only the
+    /// generals and the occupied cities are reinforced, otherwise all the occupied tiles are.
+    pub fn needs_reinforcement(&self, reinforce_all_tiles: bool) -> bool {
+        self.kind != TileKind::Mountain
+            // reinforce open tiles only when there's a global reinforcement round
+            && ((self.owner.is_some() && reinforce_all_tiles)
+                // reinforce generals every round
+                || self.kind == TileKind::General
+                // reinfoce city every round if they are occupied
+                || (self.kind == TileKind::City && self.owner.is_some()))
+    }
+}
+
 /// Represent an open tile. Open tiles are tiles that are not mountains, ie tiles that players can
 /// conquer.
 #[derive(Clone, PartialEq, Debug, Serialize)]
@@ -134,6 +344,10 @@ pub struct Tile {
     /// Players that had visibility on this tile when it changed.
     #[serde(skip)]
     dirty_for: HashSet<PlayerId>,
+
+    /// Players that had visibility on this tile at some point during the game.
+    #[serde(skip)]
+    discovered_by: HashSet<PlayerId>,
 }
 
 /// Small helper used by serde to avoid serializing the `kind` field if the tile if of type
@@ -156,6 +370,7 @@ impl Tile {
             units: 0,
             dirty_for: HashSet::new(),
             visible_by: HashSet::new(),
+            discovered_by: HashSet::new(),
             kind: TileKind::Mountain,
         }
     }
@@ -165,6 +380,11 @@ impl Tile {
         self.visible_by.contains(&player)
     }
 
+    /// Return whether the given player had visibility on the tile at some point during the game.
+    pub fn is_discovered_by(&self, player: PlayerId) -> bool {
+        self.discovered_by.contains(&player)
+    }
+
     /// Mark the tile as invisible for the given player
     pub fn hide_from(&mut self, player: PlayerId) {
         let was_visible = self.visible_by.remove(&player);
@@ -177,72 +397,33 @@ impl Tile {
     /// state if necessary (number of units, owner, etc.).
     pub fn reveal_to(&mut self, player: PlayerId) {
         self.visible_by.insert(player);
+        self.discovered_by.insert(player);
         self.dirty_for.insert(player);
     }
 
-    /// Perform a move from a source tile to a destination tile.
-    pub fn attack(&mut self, dst: &mut Tile) -> Result<MoveOutcome, InvalidMove> {
-        if self.is_mountain() {
-            return Err(InvalidMove::FromInvalidTile);
-        }
-        if dst.is_mountain() {
-            return Err(InvalidMove::ToInvalidTile);
-        }
-        if self.units() < 2 {
-            return Err(InvalidMove::NotEnoughUnits);
-        }
-        let attacker = self.owner.ok_or(InvalidMove::SourceTileNotOwned)?;
-
-        let outcome = match dst.owner {
-            // The destination tile belongs to someone else
-            Some(defender) if defender != attacker => {
-                // The defender has more units.
-                if dst.units >= self.units - 1 {
-                    dst.units -= self.units - 1;
-                    MoveOutcome::StatuQuo
-                }
-                // The attacker has more units. Capture the tile.
-                else {
-                    dst.units = self.units - 1 - dst.units;
-                    dst.owner = self.owner;
-                    // We're capturing a general
-                    if dst.kind == TileKind::General {
-                        //  Turn the general into a regular city
-                        dst.kind = TileKind::City;
-                        MoveOutcome::GeneralCaptured(defender)
-                    }
-                    // We're capturing a regular tile
-                    else {
-                        MoveOutcome::TileCaptured(Some(defender))
-                    }
-                }
-            }
-            // The owner is the same for both tiles, just transfer the unit
-            Some(_defender) => {
-                dst.units += self.units - 1;
-                MoveOutcome::StatuQuo
-            }
-            // The destination tile is not owned by anyone.
-            None => {
-                // The destination has more units, we can't capture it
-                if dst.units >= self.units - 1 {
-                    dst.units -= self.units - 1;
-                    MoveOutcome::StatuQuo
-                } else {
-                    dst.units = self.units - 1 - dst.units;
-                    dst.owner = self.owner;
-                    MoveOutcome::TileCaptured(None)
-                }
-            }
-        };
-        // In any case, we always only leave 1 unit in the source tile
-        // TODO: would be nice to support splitting the source tile units before moving.
-        self.units = 1;
+    /// Perform a move from a source tile to a destination tile. See `TileState::attack`.
+    pub fn attack(&mut self, dst: &mut Tile, teams: &Teams) -> Result<MoveOutcome, InvalidMove> {
+        let mut src_state = self.state();
+        let mut dst_state = dst.state();
+        let outcome = src_state.attack(&mut dst_state, teams)?;
+        self.units = src_state.units;
+        dst.owner = dst_state.owner;
+        dst.units = dst_state.units;
+        dst.kind = dst_state.kind;
         self.set_dirty();
         dst.set_dirty();
         Ok(outcome)
     }
 
+    /// Return the part of the tile's state the game rules depend on.
+    pub fn state(&self) -> TileState {
+        TileState {
+            kind: self.kind,
+            owner: self.owner,
+            units: self.units,
+        }
+    }
+
     /// Return the owner of the tile, if any
     pub fn owner(&self) -> Option<PlayerId> {
         self.owner
@@ -291,12 +472,11 @@ impl Tile {
         self.set_dirty();
     }
 
-    // // FIXME: unused for now, but that's because we don't have city yet
-    // /// Turn the tile into a fortess.
-    // pub fn make_city(&mut self) {
-    //     self.kind = TileKind::City;
-    //     self.set_dirty();
-    // }
+    /// Turn the tile into a city.
+    pub fn make_city(&mut self) {
+        self.kind = TileKind::City;
+        self.set_dirty();
+    }
 
     /// Turn the tile into a mountain.
     pub fn make_mountain(&mut self) {
@@ -179,7 +179,7 @@ fn conquer_occupied_tile_1() {
     dst.set_owner(Some(2));
     dst.set_units(2);
 
-    let outcome = src.attack(&mut dst).unwrap();
+    let outcome = src.attack(&mut dst, &Teams::default()).unwrap();
     assert_eq!(outcome, MoveOutcome::TileCaptured(Some(2)));
     assert_eq!(src.units(), 1);
     assert_eq!(dst.units(), 2);
@@ -198,7 +198,7 @@ fn conquer_occupied_tile_2() {
     dst.set_owner(Some(2));
     dst.set_units(2);
 
-    let outcome = src.attack(&mut dst).unwrap();
+    let outcome = src.attack(&mut dst, &Teams::default()).unwrap();
     assert_eq!(outcome, MoveOutcome::TileCaptured(Some(2)));
     assert_eq!(src.units(), 1);
     assert_eq!(dst.units(), 3);
@@ -215,7 +215,7 @@ fn conquer_unoccupied_tile() {
     let mut dst = Tile::new();
     dst.make_open();
 
-    let outcome = src.attack(&mut dst).unwrap();
+    let outcome = src.attack(&mut dst, &Teams::default()).unwrap();
     assert_eq!(outcome, MoveOutcome::TileCaptured(None));
     assert_eq!(src.units(), 1);
     assert_eq!(dst.units(), 5);
@@ -234,7 +234,7 @@ fn conquer_occupied_tile_fail() {
     dst.set_owner(Some(2));
     dst.set_units(2);
 
-    let outcome = src.attack(&mut dst).unwrap();
+    let outcome = src.attack(&mut dst, &Teams::default()).unwrap();
     assert_eq!(outcome, MoveOutcome::StatuQuo);
     assert_eq!(src.units(), 1);
     assert_eq!(dst.units(), 0);
@@ -253,7 +253,7 @@ fn conquer_unoccupied_city_fail() {
     dst.set_owner(None);
     dst.set_units(9);
 
-    let outcome = src.attack(&mut dst).unwrap();
+    let outcome = src.attack(&mut dst, &Teams::default()).unwrap();
     assert_eq!(outcome, MoveOutcome::StatuQuo);
     assert_eq!(src.units(), 1);
     assert_eq!(dst.units(), 0);
@@ -272,7 +272,7 @@ fn conquer_city() {
     dst.set_owner(None);
     dst.set_units(7);
 
-    let outcome = src.attack(&mut dst).unwrap();
+    let outcome = src.attack(&mut dst, &Teams::default()).unwrap();
     assert_eq!(outcome, MoveOutcome::TileCaptured(None));
     assert_eq!(src.units(), 1);
     assert_eq!(dst.units(), 2);
@@ -289,14 +289,14 @@ fn invalid_moves() {
     dst.set_owner(None);
     dst.set_units(7);
 
-    let outcome = src.attack(&mut dst);
+    let outcome = src.attack(&mut dst, &Teams::default());
     assert_eq!(outcome, Err(InvalidMove::FromInvalidTile));
 
     // source tile is open but has no owner
     src.make_open();
     src.set_units(10);
 
-    let outcome = src.attack(&mut dst);
+    let outcome = src.attack(&mut dst, &Teams::default());
     assert_eq!(outcome, Err(InvalidMove::SourceTileNotOwned));
 
     // source tile is open and has an owner but has not enough unit
@@ -304,12 +304,79 @@ fn invalid_moves() {
     src.set_owner(Some(1));
     src.set_units(1);
 
-    let outcome = src.attack(&mut dst);
+    let outcome = src.attack(&mut dst, &Teams::default());
     assert_eq!(outcome, Err(InvalidMove::NotEnoughUnits));
 
     // source tile is now valid but dest tile is a mountain
     src.set_units(9);
     let mut dst_mountain = Tile::new(); // we don't have a `make_mountain()` method
-    let outcome = src.attack(&mut dst_mountain);
+    let outcome = src.attack(&mut dst_mountain, &Teams::default());
     assert_eq!(outcome, Err(InvalidMove::ToInvalidTile));
 }
+
+#[test]
+fn action_move_to_deserialize() {
+    let action: Action = serde_json::from_str(r#"{"type":"move_to","from":3,"to":42}"#).unwrap();
+    match action {
+        Action::MoveTo { from, to } => assert_eq!((from, to), (3, 42)),
+        _ => panic!("unexpected action {:?}", action),
+    }
+}
+
+#[test]
+fn stamped_action_deserialize() {
+    let stamped: StampedAction =
+        serde_json::from_str(r#"{"type":"move","from":3,"direction":"up","turn":40,"seq":7}"#)
+            .unwrap();
+    assert_eq!((stamped.turn, stamped.seq), (Some(40), Some(7)));
+    match stamped.action {
+        Action::Move(mv) => assert_eq!((mv.from, mv.direction), (3, Direction::Up)),
+        _ => panic!("unexpected action {:?}", stamped.action),
+    }
+    assert!(stamped.action.queues_moves());
+
+    // The turn and the sequence number are optional
+    let stamped: StampedAction = serde_json::from_str(r#"{"type":"cancel_moves"}"#).unwrap();
+    assert_eq!((stamped.turn, stamped.seq), (None, None));
+    assert!(!stamped.action.queues_moves());
+}
+
+#[test]
+fn move_to_allied_tile() {
+    let mut player_1 = Player::new(1);
+    player_1.team = Some(0);
+    let mut player_2 = Player::new(2);
+    player_2.team = Some(0);
+    let teams = Teams::new(&[player_1, player_2, Player::new(3)]);
+    assert!(teams.are_allies(1, 2));
+    assert!(!teams.are_allies(1, 3));
+    assert!(teams.all_allies(vec![1, 2]));
+    assert!(!teams.all_allies(vec![1, 2, 3]));
+
+    let mut src = Tile::new();
+    src.make_open();
+    src.set_owner(Some(1));
+    src.set_units(5);
+
+    // The units are added to the allied tile, which changes hands
+    let mut dst = Tile::new();
+    dst.make_open();
+    dst.set_owner(Some(2));
+    dst.set_units(7);
+    let outcome = src.attack(&mut dst, &teams).unwrap();
+    assert_eq!(outcome, MoveOutcome::TileCaptured(Some(2)));
+    assert_eq!(src.units(), 1);
+    assert_eq!(dst.units(), 11);
+    assert_eq!(dst.owner(), Some(1));
+
+    // An allied general stays with its owner
+    src.set_units(5);
+    let mut general = Tile::new();
+    general.make_general();
+    general.set_owner(Some(2));
+    general.set_units(7);
+    let outcome = src.attack(&mut general, &teams).unwrap();
+    assert_eq!(outcome, MoveOutcome::StatuQuo);
+    assert_eq!(general.units(), 11);
+    assert_eq!(general.owner(), Some(2));
+}
@@ -1,22 +1,116 @@
-use super::common::{Move, Player, PlayerId, Tile};
+use super::common::{Move, MoveOutcome, Player, PlayerId, TeamId, Teams, Tile};
+use super::grid::Topology;
 use super::map::Map;
+use super::map_file::{MapError, MapFile};
+use super::map_generator::{GeneratorConfig, GeneratorError};
+use super::observation::Observation;
+use super::queue::{MoveQueue, QueueState};
+use super::sim::State;
 use std::collections::HashMap;
 
+/// What happens to the tiles of a player who resigns.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ResignPolicy {
+    /// The tiles become neutral, with their units. The player's general becomes a neutral city.
+    Neutral,
+    /// The tiles stay owned by the player, whose abandoned faction stops playing: capturing its
+    /// general still gives all its tiles to the attacker.
+    #[default]
+    Abandoned,
+}
+
+impl ResignPolicy {
+    /// Return the policy with the given name: `neutral` or `abandoned`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "neutral" => Some(ResignPolicy::Neutral),
+            "abandoned" => Some(ResignPolicy::Abandoned),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to the tiles of a player whose general is captured.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum CaptureRule {
+    /// All the tiles go to the attacker, with all their units.
+    #[default]
+    Full,
+    /// All the tiles go to the attacker, but only keep half of their units, rounded up.
+    Halve,
+}
+
+impl CaptureRule {
+    /// Return the rule with the given name: `full` or `halve`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "full" => Some(CaptureRule::Full),
+            "halve" => Some(CaptureRule::Halve),
+            _ => None,
+        }
+    }
+
+    /// Return the number of units left on a tile that is transferred to an attacker.
+    pub fn transferred_units(self, units: u16) -> u16 {
+        match self {
+            CaptureRule::Full => units,
+            CaptureRule::Halve => units.div_ceil(2),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Game {
     pub(crate) map: Map,
     pub(crate) players: HashMap<PlayerId, Player>,
     pub(crate) turn: usize,
+    pub(crate) resign_policy: ResignPolicy,
+    pub(crate) capture_rule: CaptureRule,
+    /// Name of the map, for games played on a map file
+    pub(crate) map_name: Option<String>,
 }
 
 impl Game {
     /// Create a new gmae for the given players. The map that is generated for the game gets bigger
     /// as the number of players increases. A game start at turn 0, with each player owning exactly
-    /// one tile, their general.
-    pub fn new(mut players: Vec<PlayerId>) -> Self {
-        info!("starting a new game for player {:?}", players);
+    /// one tile, their general. Return an error if the players do not fit on the map.
+    pub fn new(players: Vec<PlayerId>) -> Result<Self, GeneratorError> {
+        Self::with_generator(players, &GeneratorConfig::default())
+    }
+
+    /// Create a new game for the given players, like `Game::new`, but on a map generated with the
+    /// given settings. The map is not symmetric if the symmetry is not made for this number of
+    /// players.
+    pub fn with_generator(
+        players: Vec<PlayerId>,
+        config: &GeneratorConfig,
+    ) -> Result<Self, GeneratorError> {
+        let (generals, map) = Map::generate(players.len(), config)?;
+        Ok(Self::from_map(players, generals, map))
+    }
 
-        let (generals, map) = Map::generate(players.len());
+    /// Create a new game for the given players, like `Game::new`. The same seed always generates
+    /// the same map, and spawns the players at the same positions.
+    pub fn with_seed(players: Vec<PlayerId>, seed: u64) -> Result<Self, GeneratorError> {
+        let (generals, map) = Map::generate_with_seed(players.len(), seed)?;
+        Ok(Self::from_map(players, generals, map))
+    }
+
+    /// Create a new game for the given players, on the map described by the given map file. The
+    /// players are spawned in the order of the map's spawn points. Return an error if the map is
+    /// not valid, or if it's not made for this number of players.
+    pub fn with_map_file(players: Vec<PlayerId>, map_file: &MapFile) -> Result<Self, MapError> {
+        if map_file.nb_players() != players.len() {
+            return Err(MapError::IncompatiblePlayers(map_file.nb_players()));
+        }
+        let (generals, map) = Map::from_file(map_file)?;
+        let mut game = Self::from_map(players, generals, map);
+        game.map_name = map_file.name.clone();
+        Ok(game)
+    }
+
+    pub(crate) fn from_map(mut players: Vec<PlayerId>, generals: Vec<usize>, map: Map) -> Self {
+        info!("starting a new game for player {:?}", players);
         assert_eq!(generals.len(), players.len());
 
         for (general, player) in generals.into_iter().zip(players.iter().cloned()) {
@@ -30,6 +124,9 @@ impl Game {
             map,
             players: HashMap::with_capacity(players.len()),
             turn: 0,
+            resign_policy: ResignPolicy::default(),
+            capture_rule: CaptureRule::default(),
+            map_name: None,
         };
         for player_id in players.drain(..) {
             let mut player = Player::new(player_id);
@@ -41,17 +138,90 @@ impl Game {
         game
     }
 
+    /// Return the name of the map, if the game is played on a map file
+    pub fn map_name(&self) -> Option<&str> {
+        self.map_name.as_deref()
+    }
+
     /// Return the current turn number
     pub fn turn(&self) -> usize {
         self.turn
     }
 
+    /// Return the number of tiles in a row of the map
+    pub fn width(&self) -> usize {
+        self.map.width()
+    }
+
+    /// Put the given player in the given team. Teammates share their vision, can move through
+    /// each other's tiles, and win together. This must be done before the game starts.
+    pub fn set_team(&mut self, player: PlayerId, team: TeamId) {
+        self.players.get_mut(&player).expect("Unknown player").team = Some(team);
+    }
+
+    /// Return the teams of the players.
+    pub fn teams(&self) -> Teams {
+        Teams::new(self.players.values())
+    }
+
+    /// Return whether the game is over, ie whether all the players that have not been defeated
+    /// yet are allies. Without teams, this means at most one player has not been defeated yet.
+    /// Note that players who lost all their tiles are only marked as defeated when an update is
+    /// built (see `Game::get_update`).
+    pub fn is_over(&self) -> bool {
+        self.teams().all_allies(
+            self.players
+                .values()
+                .filter(|p| !p.defeated())
+                .map(|p| p.id),
+        )
+    }
+
+    /// Return the player who won the game, if the game is over and someone won it. In team games,
+    /// this is one of the players of the winning team who have not been defeated: see
+    /// `Game::winners`.
+    pub fn winner(&self) -> Option<PlayerId> {
+        if !self.is_over() {
+            return None;
+        }
+        self.players.values().find(|p| !p.defeated()).map(|p| p.id)
+    }
+
+    /// Return all the players who won the game, including the defeated players of the winning
+    /// team. The list is empty if the game is not over, or if nobody won it.
+    pub fn winners(&self) -> Vec<PlayerId> {
+        let winner = match self.winner() {
+            Some(winner) => winner,
+            None => return Vec::new(),
+        };
+        let teams = self.teams();
+        let mut winners: Vec<PlayerId> = self
+            .players
+            .keys()
+            .filter(|player| teams.are_allies(winner, **player))
+            .cloned()
+            .collect();
+        winners.sort();
+        winners
+    }
+
+    /// Set what happens to the tiles of the players who resign. See `ResignPolicy`.
+    pub fn set_resign_policy(&mut self, policy: ResignPolicy) {
+        self.resign_policy = policy;
+    }
+
+    /// Set what happens to the tiles of the players whose general is captured. See
+    /// `CaptureRule`.
+    pub fn set_capture_rule(&mut self, rule: CaptureRule) {
+        self.capture_rule = rule;
+    }
+
     /// Mark the given player as defeated. When a player is defeated he cannot perform any action
-    /// anymore. Note that this method does not take care of the tiles owned by the player
-    /// resigning so there are two cases:
+    /// anymore, and his tiles are not reinforced anymore. There are two cases:
     ///
     /// - This method was called because the player performed the action of resigning, in which
-    ///   case it still owns some tiles, that will keep beeing reinforced
+    ///   case it still owns some tiles, that are handled according to the resign policy (see
+    ///   `ResignPolicy`)
     /// - The player's general has been captured, in which case all his tiles have already been
     ///   transfered to the player that captured him
     pub fn resign(&mut self, id: PlayerId) {
@@ -59,40 +229,140 @@ impl Game {
         let player = self.players.get_mut(&id).expect("Unknown player");
         if player.defeated() {
             error!("Got resignation from player defeated at turn {}", self.turn);
-        } else {
-            player.defeated_at = Some(self.turn);
+            return;
         }
+        player.defeated_at = Some(self.turn);
+        if self.resign_policy == ResignPolicy::Neutral {
+            self.map.release(id);
+        }
+    }
+
+    /// Return the players who have been defeated.
+    fn defeated_players(&self) -> Vec<PlayerId> {
+        self.players
+            .values()
+            .filter(|p| p.defeated())
+            .map(|p| p.id)
+            .collect()
     }
 
     /// Process the given move, and update the game state. If the move is invalid (between tiles
     /// that are not adjacent, or from a tile that does not belong to the player making the move,
-    /// for example), it is simply ignored. Not error is returned. Tiles that are updated by the
-    /// move are marked as dirty.
-    pub fn perform_move(&mut self, mv: Move) {
+    /// for example), it is simply ignored and `None` is returned. Otherwise, the outcome of the
+    /// move is returned. Tiles that are updated by the move are marked as dirty.
+    pub fn perform_move(&mut self, mv: Move) -> Option<MoveOutcome> {
         info!("processing move {:?}", mv);
         if let Some(player) = self.players.get(&mv.player) {
             if !player.can_move() {
                 warn!("player {} cannot move, ignoring the move", mv.player);
-                return;
+                return None;
             }
-            if let Err(e) = self.map.perform_move(mv) {
-                warn!("failed to process move {:?}: {}", mv, e);
+            match self.map.perform_move(mv, &self.teams(), self.capture_rule) {
+                Ok(outcome) => Some(outcome),
+                Err(e) => {
+                    warn!("failed to process move {:?}: {}", mv, e);
+                    None
+                }
             }
         } else {
             warn!("unknown player {}, ignoring the move", mv.player);
+            None
+        }
+    }
+
+    /// Return whether `Game::perform_move` would accept the given move.
+    pub fn is_legal(&self, mv: Move) -> bool {
+        match self.players.get(&mv.player) {
+            Some(player) if player.can_move() => self.map.predict_move(mv, &self.teams()).is_ok(),
+            _ => false,
         }
     }
 
-    /// Increment the number of units on tiles that are owned by players.
-    /// Regular tiles are reinforced once every 25 turns, but generals and cityes are
+    /// Return the moves of the shortest path the given player can follow to move units from a
+    /// tile to another. The path only crosses tiles that look open to the player (see
+    /// `Map::path`). Return `None` if there is no such path.
+    pub fn path(&self, player: PlayerId, from: usize, to: usize) -> Option<Vec<Move>> {
+        let path = self.map.path(player, from, to)?;
+        let moves = path
+            .windows(2)
+            .map(|step| Move {
+                player,
+                from: step[0],
+                direction: self.map.direction(step[0], step[1]).unwrap(),
+            })
+            .collect();
+        Some(moves)
+    }
+
+    /// Return all the moves the given player can make this turn, ie all the moves that
+    /// `Game::perform_move` would accept, with their predicted outcome. Bots should rely on this
+    /// instead of reimplementing the rules.
+    pub fn legal_moves_with_outcome(&self, player: PlayerId) -> Vec<(Move, MoveOutcome)> {
+        match self.players.get(&player) {
+            Some(p) if p.can_move() => self.map.legal_moves(player, &self.teams()),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Return all the moves the given player can make this turn. See
+    /// `Game::legal_moves_with_outcome`.
+    pub fn legal_moves(&self, player: PlayerId) -> Vec<Move> {
+        self.legal_moves_with_outcome(player)
+            .into_iter()
+            .map(|(mv, _)| mv)
+            .collect()
+    }
+
+    /// Return the number of tiles and the number of units owned by the given player.
+    pub fn land_and_army(&self, player: PlayerId) -> (usize, usize) {
+        self.map
+            .iter()
+            .filter(|tile| tile.owner() == Some(player))
+            .fold((0, 0), |(land, army), tile| {
+                (land + 1, army + tile.units() as usize)
+            })
+    }
+
+    /// Return a snapshot of the game that can be used to simulate future turns. See `State`.
+    pub fn state(&self) -> State {
+        State::new(self)
+    }
+
+    /// Return the given player's view of the map, encoded as planes of the given dimensions.
+    /// Teammates share their vision, like in `Update::filtered`. See `Observation::encode`.
+    pub fn observe(&self, player: PlayerId, max_width: usize, max_height: usize) -> Observation {
+        let teams = self.teams();
+        let allies: Vec<PlayerId> = self
+            .players
+            .keys()
+            .filter(|other| **other != player && teams.are_allies(player, **other))
+            .cloned()
+            .collect();
+        Observation::encode(&self.map, player, &allies, max_width, max_height)
+    }
+
+    /// Return whether the given player has been defeated. Unknown players are considered
+    /// defeated.
+    pub fn is_defeated(&self, player: PlayerId) -> bool {
+        self.players.get(&player).is_none_or(Player::defeated)
+    }
+
+    /// Increment the number of units on tiles that are owned by players who have not been
+    /// defeated. Regular tiles are reinforced once every 25 turns, but generals and cityes are
     /// reinforced at every turn.
     pub fn reinforce(&mut self) {
-        if self.turn % 50 == 0 {
-            info!("reinforcing all the tiles");
-            self.map.reinforce(true);
-        } else if self.turn % 2 == 0 {
-            info!("reinforcing generals and cities");
-            self.map.reinforce(false);
+        match reinforcement(self.turn) {
+            Some(true) => {
+                info!("reinforcing all the tiles");
+                let defeated = self.defeated_players();
+                self.map.reinforce(true, &defeated);
+            }
+            Some(false) => {
+                info!("reinforcing generals and cities");
+                let defeated = self.defeated_players();
+                self.map.reinforce(false, &defeated);
+            }
+            None => {}
         }
     }
 
@@ -132,7 +402,8 @@ impl Game {
                         panic!("Tile {:?} owned by an unknown player {}", tile, owner);
                     }
                 }
-                if is_first_turn || tile.is_dirty() {
+                // Void cells never change, and are sent apart in the first update.
+                if (is_first_turn || tile.is_dirty()) && !map.is_void(i) {
                     updated_tiles.push((i, tile.clone()));
                     tile.set_clean();
                 }
@@ -155,8 +426,24 @@ impl Game {
             players: self.players.clone(),
             width: self.map.width(),
             height: self.map.height(),
+            topology: self.map.topology(),
             is_initial_update: self.is_first_turn(),
             tiles: updated_tiles,
+            map: if self.is_first_turn() {
+                self.map_name.clone()
+            } else {
+                None
+            },
+            void: if self.is_first_turn() {
+                (0..self.map.len())
+                    .filter(|index| self.map.is_void(*index))
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            player: None,
+            queue: None,
+            ack: None,
         }
     }
 
@@ -165,33 +452,143 @@ impl Game {
     }
 }
 
+/// Return which tiles must be reinforced at the given turn: `Some(true)` if all the occupied
+/// tiles must be reinforced, `Some(false)` if only the generals and cities must be, and `None` if
+/// no tile must be reinforced.
+pub(crate) fn reinforcement(turn: usize) -> Option<bool> {
+    if turn % 50 == 0 {
+        Some(true)
+    } else if turn % 2 == 0 {
+        Some(false)
+    } else {
+        None
+    }
+}
+
 #[derive(Serialize, Clone)]
 pub struct Update {
     turn: usize,
     width: usize,
     height: usize,
+    /// The topology of the map, if it's not flat
+    #[serde(skip_serializing_if = "Topology::is_flat")]
+    topology: Topology,
     players: HashMap<PlayerId, Player>,
     tiles: Vec<(usize, Tile)>,
+    /// The name of the map, in the updates of turn 0 of games played on a map file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    map: Option<String>,
+    /// The cells that are out of the map, in the updates of turn 0
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    void: Vec<usize>,
+    /// The player the update is filtered for, in filtered updates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    player: Option<PlayerId>,
+    /// The state of the player's queue of moves, in filtered updates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    queue: Option<QueueState>,
+    /// The sequence number of the last action processed for the player, in filtered updates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ack: Option<u64>,
     #[serde(skip)]
     is_initial_update: bool,
 }
 
 impl Update {
+    /// Return the turn of the update
+    pub fn turn(&self) -> usize {
+        self.turn
+    }
+
+    /// Return the number of tiles in a row
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Return the number of tiles in a column
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Return the topology of the map
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Return the name of the map the game is played on. It's only given in the updates of turn 0,
+    /// for games played on a map file.
+    pub fn map(&self) -> Option<&str> {
+        self.map.as_deref()
+    }
+
+    /// Return the indices of the void cells, which are out of the map. They're only given in the
+    /// updates of turn 0, and they're not part of the tiles of any update.
+    pub fn void(&self) -> &[usize] {
+        &self.void
+    }
+
+    /// Return the player the update is filtered for, if it's a filtered update
+    pub fn player(&self) -> Option<PlayerId> {
+        self.player
+    }
+
+    /// Return all the players, including the defeated ones
+    pub fn players(&self) -> impl Iterator<Item = &Player> {
+        self.players.values()
+    }
+
+    /// Return the tiles that changed since the previous update, with their index. The initial
+    /// update contains all the tiles.
+    pub fn tiles(&self) -> &[(usize, Tile)] {
+        &self.tiles
+    }
+
+    /// Return the update with the state of the given queue, which should be the queue of the
+    /// player the update is filtered for.
+    pub fn with_queue(mut self, queue: &MoveQueue) -> Self {
+        self.queue = Some(queue.state());
+        self
+    }
+
+    /// Return the update with the sequence number of the last action processed for the player
+    /// the update is for, if any.
+    pub fn with_ack(mut self, seq: Option<u64>) -> Self {
+        self.ack = seq;
+        self
+    }
+
+    /// Return the update as seen by the given player: the tiles in the fog of war are hidden.
+    /// Teammates share their vision, so the tiles one of them sees are visible by all of them.
     pub fn filtered(&self, player: PlayerId) -> Self {
         info!("filtering update for player {}", player);
+        let teams = Teams::new(self.players.values());
+        let allies: Vec<PlayerId> = self
+            .players
+            .keys()
+            .filter(|other| teams.are_allies(player, **other))
+            .cloned()
+            .collect();
         Update {
             turn: self.turn,
             width: self.width,
             height: self.height,
+            topology: self.topology,
             players: self.players.clone(),
             is_initial_update: self.is_initial_update,
+            map: self.map.clone(),
+            void: self.void.clone(),
+            player: Some(player),
+            queue: None,
+            ack: None,
             tiles: self
                 .tiles
                 .iter()
-                .filter(|(_, t)| t.is_dirty_for(player) || self.is_initial_update)
+                .filter(|(_, t)| {
+                    self.is_initial_update || allies.iter().any(|ally| t.is_dirty_for(*ally))
+                })
                 .map(|(i, t)| {
                     let mut t = t.clone();
-                    if !t.is_visible_by(player) {
+                    if !allies.iter().any(|ally| t.is_visible_by(*ally)) {
                         t.set_units(0);
                         if t.is_general() {
                             t.make_open();
@@ -0,0 +1,152 @@
+use std::cell::RefCell;
+
+use super::common::{Direction, Move, MoveOutcome, Tile, TileKind};
+use super::game::{CaptureRule, Game, ResignPolicy};
+use super::grid::Grid;
+use super::map::Map;
+
+const PLAYER_1: usize = 0;
+const PLAYER_2: usize = 1;
+const PLAYER_3: usize = 2;
+
+/// Return a game on a map that looks like the following, where players 1 and 2 are in the same
+/// team:
+///
+/// ```
+/// General1[5]  Open  Open  General2[5]
+/// Open         Open  Open  Open
+/// Open         Open  Open  General3[5]
+/// ```
+fn get_game() -> Game {
+    let grid = Grid::new(
+        |_| {
+            let mut tile = Tile::new();
+            tile.make_open();
+            RefCell::new(tile)
+        },
+        4,
+        3,
+    );
+    let map = Map::from_grid(grid);
+    for general in &[0, 3, 11] {
+        map.get_mut(*general).make_general();
+        map.get_mut(*general).set_units(5);
+    }
+    let mut game = Game::from_map(vec![PLAYER_1, PLAYER_2, PLAYER_3], vec![0, 3, 11], map);
+    game.set_team(PLAYER_1, 0);
+    game.set_team(PLAYER_2, 0);
+    game.set_team(PLAYER_3, 1);
+    game
+}
+
+#[test]
+fn test_shared_vision() {
+    let mut game = get_game();
+    let update = game.get_update();
+    let units = |player: usize, index: usize| {
+        update
+            .filtered(player)
+            .tiles()
+            .iter()
+            .find(|(i, _)| *i == index)
+            .map(|(_, tile)| tile.units())
+    };
+    // Player 1 sees its teammate's general, but not the general of player 3.
+    assert_eq!(units(PLAYER_1, 3), Some(5));
+    assert_eq!(units(PLAYER_1, 11), Some(0));
+    // Player 3 only sees what it can see itself
+    assert_eq!(units(PLAYER_3, 3), Some(0));
+    assert_eq!(units(PLAYER_3, 11), Some(5));
+}
+
+#[test]
+fn test_team_victory() {
+    let mut game = get_game();
+    let _ = game.get_update();
+    assert!(!game.is_over());
+    assert!(game.winners().is_empty());
+
+    game.resign(PLAYER_1);
+    assert!(!game.is_over());
+    game.resign(PLAYER_3);
+    assert!(game.is_over());
+    assert!(game.state().is_over());
+    assert_eq!(game.winner(), Some(PLAYER_2));
+    // The defeated teammate wins too
+    assert_eq!(game.winners(), vec![PLAYER_1, PLAYER_2]);
+}
+
+#[test]
+fn test_resign_neutral() {
+    let mut game = get_game();
+    game.set_resign_policy(ResignPolicy::Neutral);
+    let _ = game.get_update();
+    let mut state = game.state();
+
+    game.resign(PLAYER_3);
+    state.resign(PLAYER_3);
+    let general = game.map.get(11).state();
+    assert_eq!(general.kind, TileKind::City);
+    assert_eq!(general.owner, None);
+    assert_eq!(general.units, 5);
+    assert!(!game.map.get(11).is_visible_by(PLAYER_3));
+    assert_eq!(state.grid().tiles(), game.map.states().tiles());
+
+    // Neutral cities are not reinforced
+    game.incr_turn();
+    game.incr_turn();
+    assert_eq!(game.map.get(11).units(), 5);
+    assert_eq!(game.map.get(0).units(), 6);
+}
+
+#[test]
+fn test_resign_abandoned() {
+    let mut game = get_game();
+    let _ = game.get_update();
+    game.resign(PLAYER_3);
+    let mut state = game.state();
+
+    // The abandoned general is not reinforced anymore
+    game.incr_turn();
+    game.incr_turn();
+    let _ = state.apply(&[]);
+    let _ = state.apply(&[]);
+    let general = game.map.get(11).state();
+    assert_eq!(general.kind, TileKind::General);
+    assert_eq!(general.owner, Some(PLAYER_3));
+    assert_eq!(general.units, 5);
+    assert_eq!(game.map.get(0).units(), 6);
+    assert_eq!(state.grid().tiles(), game.map.states().tiles());
+}
+
+#[test]
+fn test_capture_halve() {
+    let mut game = get_game();
+    game.set_capture_rule(CaptureRule::Halve);
+    let _ = game.get_update();
+    game.map.get_mut(10).set_owner(Some(PLAYER_3));
+    game.map.get_mut(10).set_units(5);
+    game.map.get_mut(7).set_owner(Some(PLAYER_2));
+    game.map.get_mut(7).set_units(20);
+    let mut state = game.state();
+
+    let mv = Move {
+        player: PLAYER_2,
+        from: 7,
+        direction: Direction::Down,
+    };
+    let outcome = game.perform_move(mv);
+    assert_eq!(outcome, Some(MoveOutcome::GeneralCaptured(PLAYER_3)));
+    assert_eq!(state.apply(&[mv]), vec![outcome]);
+    // The general becomes a city of the attacker, and the other tiles of the defeated player
+    // keep half of their units.
+    let general = game.map.get(11).state();
+    assert_eq!(general.kind, TileKind::City);
+    assert_eq!(general.owner, Some(PLAYER_2));
+    assert_eq!(general.units, 14);
+    assert_eq!(game.map.get(10).owner(), Some(PLAYER_2));
+    assert_eq!(game.map.get(10).units(), 3);
+
+    game.incr_turn();
+    assert_eq!(state.grid().tiles(), game.map.states().tiles());
+}
@@ -1,8 +1,41 @@
 use std::slice::Iter;
 
-#[derive(Debug)]
+use super::common::Direction;
+
+/// How the edges of a grid are connected.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Topology {
+    /// The edges are the limits of the grid.
+    #[default]
+    Flat,
+    /// Moving off an edge reappears on the opposite edge, both horizontally and vertically.
+    Torus,
+}
+
+impl Topology {
+    /// Return the topology with the given name: `flat` or `torus`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "flat" => Some(Topology::Flat),
+            "torus" => Some(Topology::Torus),
+            _ => None,
+        }
+    }
+
+    /// Return whether the topology is flat. This is used by serde to skip the default topology.
+    pub fn is_flat(&self) -> bool {
+        *self == Topology::Flat
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Grid<T> {
     tiles: Vec<T>,
+    /// Whether each cell is void, ie out of play. Void cells are not the neighbors of any cell,
+    /// which gives grids arbitrary shapes.
+    void: Vec<bool>,
+    topology: Topology,
     width: usize,
     height: usize,
 }
@@ -15,17 +48,49 @@ impl<T> Grid<T> {
         let nb_tiles = width * height;
         Grid {
             tiles: (0..nb_tiles).map(factory).collect(),
+            void: vec![false; nb_tiles],
+            topology: Topology::Flat,
             width,
             height,
         }
     }
 
+    /// Return a new grid with the same dimensions, where each tile is converted with the given
+    /// function.
+    pub fn map_tiles<U, F>(&self, f: F) -> Grid<U>
+    where
+        F: Fn(&T) -> U,
+    {
+        Grid {
+            tiles: self.tiles.iter().map(f).collect(),
+            void: self.void.clone(),
+            topology: self.topology,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Return the topology of the grid.
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Set the topology of the grid, which changes the neighbors of the cells on the edges.
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+
+    /// Return the manhattan distance between two cells. On toroidal grids, the shortest way
+    /// around is taken.
     pub fn manhattan_distance(&self, i1: usize, i2: usize) -> usize {
         let (c1, l1) = self.coordinates(i1);
         let (c2, l2) = self.coordinates(i2);
-        let x = if c1 <= c2 { c2 - c1 } else { c1 - c2 };
-        let y = if l1 <= l2 { l2 - l1 } else { l1 - l2 };
-        x + y
+        let x = c1.abs_diff(c2);
+        let y = l1.abs_diff(l2);
+        match self.topology {
+            Topology::Flat => x + y,
+            Topology::Torus => x.min(self.width - x) + y.min(self.height - y),
+        }
     }
 
     pub fn tiles(&self) -> &[T] {
@@ -40,8 +105,33 @@ impl<T> Grid<T> {
         column + line * self.width
     }
 
+    /// Return whether the given index is the index of a cell of the grid that is not void.
     pub fn is_valid_index(&self, i: usize) -> bool {
-        i < self.width * self.height
+        i < self.width * self.height && !self.void[i]
+    }
+
+    /// Return whether the cell at the given index is void.
+    pub fn is_void(&self, index: usize) -> bool {
+        self.void[index]
+    }
+
+    /// Make the cell at the given index void. It's not a neighbor of any cell anymore.
+    pub fn make_void(&mut self, index: usize) {
+        self.void[index] = true;
+    }
+
+    /// Return the number of cells that are not void.
+    pub fn nb_playable(&self) -> usize {
+        self.void.iter().filter(|void| !**void).count()
+    }
+
+    /// Return the given index, unless the cell is void.
+    fn playable(&self, index: usize) -> Option<usize> {
+        if self.void[index] {
+            None
+        } else {
+            Some(index)
+        }
     }
 
     fn coordinates(&self, i: usize) -> (usize, usize) {
@@ -56,92 +146,84 @@ impl<T> Grid<T> {
         i / self.width
     }
 
-    pub fn up_left(&self, index: usize) -> Option<usize> {
+    /// Return the index of the cell at the given offset from the given one, if it is on the grid
+    /// and not void. On toroidal grids, the offset wraps around the edges, but a cell is never
+    /// its own neighbor.
+    fn offset(&self, index: usize, columns: isize, lines: isize) -> Option<usize> {
         if !self.is_valid_index(index) {
             return None;
         }
         let (column, line) = self.coordinates(index);
-        if column == 0 || line == 0 {
+        let column = self.wrap(column as isize + columns, self.width)?;
+        let line = self.wrap(line as isize + lines, self.height)?;
+        let neighbor = self.index(column, line);
+        if neighbor == index {
             return None;
         }
-        Some(self.index(column - 1, line - 1))
+        self.playable(neighbor)
     }
 
-    pub fn up(&self, index: usize) -> Option<usize> {
-        if !self.is_valid_index(index) {
-            return None;
-        }
-        let (column, line) = self.coordinates(index);
-        if line == 0 {
-            return None;
+    /// Return the given coordinate, if it is within the given size. On toroidal grids, the
+    /// coordinate is wrapped around instead.
+    fn wrap(&self, coordinate: isize, size: usize) -> Option<usize> {
+        match self.topology {
+            Topology::Torus => Some(coordinate.rem_euclid(size as isize) as usize),
+            Topology::Flat if coordinate >= 0 && (coordinate as usize) < size => {
+                Some(coordinate as usize)
+            }
+            Topology::Flat => None,
         }
-        Some(self.index(column, line - 1))
+    }
+
+    pub fn up_left(&self, index: usize) -> Option<usize> {
+        self.offset(index, -1, -1)
+    }
+
+    pub fn up(&self, index: usize) -> Option<usize> {
+        self.offset(index, 0, -1)
     }
 
     pub fn up_right(&self, index: usize) -> Option<usize> {
-        if !self.is_valid_index(index) {
-            return None;
-        }
-        let (column, line) = self.coordinates(index);
-        if column == self.width() - 1 || line == 0 {
-            return None;
-        }
-        Some(self.index(column + 1, line - 1))
+        self.offset(index, 1, -1)
     }
 
     pub fn left(&self, index: usize) -> Option<usize> {
-        if !self.is_valid_index(index) {
-            return None;
-        }
-        let (column, line) = self.coordinates(index);
-        if column == 0 {
-            return None;
-        }
-        Some(self.index(column - 1, line))
+        self.offset(index, -1, 0)
     }
 
     pub fn right(&self, index: usize) -> Option<usize> {
-        if !self.is_valid_index(index) {
-            return None;
-        }
-        let (column, line) = self.coordinates(index);
-        if column == self.width() - 1 {
-            return None;
-        }
-        Some(self.index(column + 1, line))
+        self.offset(index, 1, 0)
     }
 
     pub fn down_left(&self, index: usize) -> Option<usize> {
-        if !self.is_valid_index(index) {
-            return None;
-        }
-        let (column, line) = self.coordinates(index);
-        if column == 0 || line == self.height() - 1 {
-            return None;
-        }
-        Some(self.index(column - 1, line + 1))
+        self.offset(index, -1, 1)
     }
 
     pub fn down(&self, index: usize) -> Option<usize> {
-        if !self.is_valid_index(index) {
-            return None;
-        }
-        let (column, line) = self.coordinates(index);
-        if line == self.height() - 1 {
-            return None;
-        }
-        Some(self.index(column, line + 1))
+        self.offset(index, 0, 1)
     }
 
     pub fn down_right(&self, index: usize) -> Option<usize> {
-        if !self.is_valid_index(index) {
-            return None;
-        }
-        let (column, line) = self.coordinates(index);
-        if column == self.width() - 1 || line == self.height() - 1 {
-            return None;
+        self.offset(index, 1, 1)
+    }
+
+    /// Return the index of the tile next to the given one in the given direction, if any.
+    pub fn neighbor(&self, index: usize, direction: Direction) -> Option<usize> {
+        match direction {
+            Direction::Right => self.right(index),
+            Direction::Left => self.left(index),
+            Direction::Up => self.up(index),
+            Direction::Down => self.down(index),
         }
-        Some(self.index(column + 1, line + 1))
+    }
+
+    /// Return the direction to go from a tile to one of its direct neighbors, or `None` if the
+    /// tiles are not direct neighbors.
+    pub fn direction(&self, from: usize, to: usize) -> Option<Direction> {
+        Direction::all()
+            .iter()
+            .cloned()
+            .find(|direction| self.neighbor(from, *direction) == Some(to))
     }
 
     pub fn width(&self) -> usize {
@@ -183,6 +265,20 @@ impl<T> Grid<T> {
     pub fn iter(&self) -> Iter<'_, T> {
         self.tiles().iter()
     }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut T {
+        &mut self.tiles[index]
+    }
+}
+
+/// Drop the neighbors that were already seen in another direction. This happens on toroidal
+/// grids that are less than 3 cells wide or high, where both directions wrap to the same cell.
+fn dedup(neighbors: &mut [Option<usize>]) {
+    for i in 1..neighbors.len() {
+        if neighbors[..i].contains(&neighbors[i]) {
+            neighbors[i] = None;
+        }
+    }
 }
 
 pub struct DirectNeighborsIter {
@@ -191,7 +287,8 @@ pub struct DirectNeighborsIter {
 }
 
 impl DirectNeighborsIter {
-    fn new(neighbors: [Option<usize>; 4]) -> Self {
+    fn new(mut neighbors: [Option<usize>; 4]) -> Self {
+        dedup(&mut neighbors);
         DirectNeighborsIter {
             count: 0,
             neighbors,
@@ -221,7 +318,8 @@ pub struct ExtendedNeighborsIter {
 }
 
 impl ExtendedNeighborsIter {
-    fn new(neighbors: [Option<usize>; 8]) -> Self {
+    fn new(mut neighbors: [Option<usize>; 8]) -> Self {
+        dedup(&mut neighbors);
         ExtendedNeighborsIter {
             count: 0,
             neighbors,
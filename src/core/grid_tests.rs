@@ -71,3 +71,74 @@ fn test_extended_neighbors() {
     let neighbors: Vec<usize> = grid.extended_neighbors(7).collect();
     assert_eq!(neighbors.as_slice(), &[3, 4, 5, 6, 8, 9, 10, 11]);
 }
+
+// 0  1  2
+// 3  X  5
+// 6  7  8
+// 9  10 11
+//
+// The cell 4 is void
+#[test]
+fn test_void() {
+    let mut grid = Grid::<u8>::new(|idx| idx as u8, 3, 4);
+    grid.make_void(4);
+    assert!(grid.is_void(4));
+    assert!(!grid.is_valid_index(4));
+    assert_eq!(grid.nb_playable(), 11);
+    assert!(grid.up(7).is_none());
+    assert!(grid.down(4).is_none());
+
+    let neighbors: Vec<usize> = grid.direct_neighbors(1).collect();
+    assert_eq!(neighbors.as_slice(), &[0, 2]);
+
+    let neighbors: Vec<usize> = grid.extended_neighbors(0).collect();
+    assert_eq!(neighbors.as_slice(), &[1, 3]);
+
+    assert!(grid.map_tiles(|tile| *tile).is_void(4));
+}
+
+// 0  1  2
+// 3  4  5
+// 6  7  8
+// 9  10 11
+//
+// On a torus, the first and last columns are neighbors, and so are the first and last lines
+#[test]
+fn test_torus() {
+    let mut grid = Grid::<u8>::new(|idx| idx as u8, 3, 4);
+    grid.set_topology(Topology::Torus);
+    assert_eq!(grid.up(1), Some(10));
+    assert_eq!(grid.down(10), Some(1));
+    assert_eq!(grid.left(3), Some(5));
+    assert_eq!(grid.right(5), Some(3));
+    assert_eq!(grid.up_left(0), Some(11));
+    assert_eq!(grid.down_right(11), Some(0));
+
+    let neighbors: Vec<usize> = grid.direct_neighbors(0).collect();
+    assert_eq!(neighbors.as_slice(), &[9, 2, 1, 3]);
+
+    let neighbors: Vec<usize> = grid.extended_neighbors(0).collect();
+    assert_eq!(neighbors.as_slice(), &[11, 9, 10, 2, 1, 5, 3, 4]);
+
+    assert_eq!(grid.manhattan_distance(0, 11), 2);
+    assert_eq!(grid.manhattan_distance(0, 7), 3);
+    assert_eq!(grid.map_tiles(|tile| *tile).topology(), Topology::Torus);
+
+    // Void cells are not neighbors across the edges either.
+    grid.make_void(2);
+    assert!(grid.left(0).is_none());
+
+    // A cell is not its own neighbor.
+    let mut line = Grid::<u8>::new(|idx| idx as u8, 1, 3);
+    line.set_topology(Topology::Torus);
+    assert!(line.left(1).is_none());
+    assert_eq!(line.up(0), Some(2));
+
+    // On a torus that is 2 cells wide, the left and right neighbors are the same cell.
+    let mut narrow = Grid::<u8>::new(|idx| idx as u8, 2, 3);
+    narrow.set_topology(Topology::Torus);
+    let neighbors: Vec<usize> = narrow.direct_neighbors(0).collect();
+    assert_eq!(neighbors.as_slice(), &[4, 1, 2]);
+    let neighbors: Vec<usize> = narrow.extended_neighbors(0).collect();
+    assert_eq!(neighbors.as_slice(), &[5, 4, 1, 3, 2]);
+}
@@ -1,10 +1,12 @@
-#[cfg(test)]
-use std::cell::Ref;
-use std::cell::{RefCell, RefMut};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::VecDeque;
 
-use super::common::{Direction, InvalidMove, Move, MoveOutcome, PlayerId, Tile};
-use super::grid::Grid;
-use super::map_generator::GridBuilder;
+use super::common::{Direction, InvalidMove, Move, MoveOutcome, PlayerId, Teams, Tile, TileState};
+use super::game::CaptureRule;
+use super::grid::{Grid, Topology};
+use super::map_file::{MapError, MapFile};
+use super::map_generator::{GeneratorConfig, GeneratorError, GridBuilder};
+use super::sim::legal_moves;
 
 /// A grid representing the game map. It provides interior mutability for the tiles, which means
 /// multiple tiles can be borrowed mutable at the same time, without having to borrow mutably the
@@ -13,11 +15,33 @@ use super::map_generator::GridBuilder;
 pub struct Map(Grid<RefCell<Tile>>);
 
 impl Map {
-    /// Return a random new map with the specified number of generals.
-    pub fn generate(nb_generals: usize) -> (Vec<usize>, Self) {
-        let grid_builder = GridBuilder::new(nb_generals);
-        let (generals, grid) = grid_builder.build();
-        (generals, Map(grid))
+    /// Return a random new map with the specified number of generals, and the given settings.
+    /// Return an error if the generals do not fit on the map.
+    pub fn generate(
+        nb_generals: usize,
+        config: &GeneratorConfig,
+    ) -> Result<(Vec<usize>, Self), GeneratorError> {
+        let grid_builder = GridBuilder::new(nb_generals).config(config);
+        let (generals, grid) = grid_builder.build()?;
+        Ok((generals, Map(grid)))
+    }
+
+    /// Return a random new map with the specified number of generals. The same seed always
+    /// generates the same map. Return an error if the generals do not fit on the map.
+    pub fn generate_with_seed(
+        nb_generals: usize,
+        seed: u64,
+    ) -> Result<(Vec<usize>, Self), GeneratorError> {
+        let grid_builder = GridBuilder::with_seed(nb_generals, seed);
+        let (generals, grid) = grid_builder.build()?;
+        Ok((generals, Map(grid)))
+    }
+
+    /// Return the map described by the given map file, with its generals in the order of the
+    /// spawn points. Return an error if the map is not valid. See `MapFile`.
+    pub fn from_file(map_file: &MapFile) -> Result<(Vec<usize>, Self), MapError> {
+        let (generals, grid) = map_file.build()?;
+        Ok((generals, Map(grid)))
     }
 
     /// The number of tiles on the map
@@ -35,6 +59,17 @@ impl Map {
         self.0.height()
     }
 
+    /// Return whether the cell at the given index is void, ie out of the map. Void cells are
+    /// mountains that are not the neighbors of any tile.
+    pub fn is_void(&self, index: usize) -> bool {
+        self.0.is_void(index)
+    }
+
+    /// Return the topology of the map.
+    pub fn topology(&self) -> Topology {
+        self.0.topology()
+    }
+
     #[cfg(test)]
     pub fn from_grid(inner: Grid<RefCell<Tile>>) -> Self {
         Map(inner)
@@ -48,20 +83,23 @@ impl Map {
     /// - update the visibility of the tiles surrounding the destination tile
     ///
     /// If a general is captured, this method also gives all the tiles that belonged to the
-    /// defeated general to the attacker, and updates the visibility of the attacker.
-    pub fn perform_move(&mut self, mv: Move) -> Result<(), InvalidMove> {
+    /// defeated general to the attacker, with the number of units given by the capture rule, and
+    /// updates the visibility of the attacker.
+    pub fn perform_move(
+        &mut self,
+        mv: Move,
+        teams: &Teams,
+        capture_rule: CaptureRule,
+    ) -> Result<MoveOutcome, InvalidMove> {
         // If the source tile is not in the grid, the move is invalid
         if !self.0.is_valid_index(mv.from) {
             return Err(InvalidMove::FromInvalidTile);
         }
 
-        let dst_idx = match mv.direction {
-            Direction::Right => self.0.right(mv.from),
-            Direction::Left => self.0.left(mv.from),
-            Direction::Up => self.0.up(mv.from),
-            Direction::Down => self.0.down(mv.from),
-        }
-        .ok_or(InvalidMove::ToInvalidTile)?;
+        let dst_idx = self
+            .0
+            .neighbor(mv.from, mv.direction)
+            .ok_or(InvalidMove::ToInvalidTile)?;
 
         let outcome = {
             let mut src = self.get_mut(mv.from);
@@ -75,7 +113,7 @@ impl Map {
                         return Err(InvalidMove::SourceTileNotOwned);
                     }
                     let mut dst = self.get_mut(dst_idx);
-                    src.attack(&mut dst)?
+                    src.attack(&mut dst, teams)?
                 }
                 None => {
                     warn!("source tile is not owned by any player");
@@ -92,6 +130,8 @@ impl Map {
                 for mut t in self.iter_mut().filter(|t| !t.is_mountain()) {
                     if t.owner() == Some(defeated_player) {
                         t.set_owner(Some(mv.player));
+                        let units = capture_rule.transferred_units(t.units());
+                        t.set_units(units);
                     }
                     if t.is_visible_by(defeated_player) {
                         t.hide_from(defeated_player);
@@ -111,7 +151,85 @@ impl Map {
             // nothing to do.
             _ => {}
         }
-        Ok(())
+        Ok(outcome)
+    }
+
+    /// Return the outcome the given move would have, without performing it. The move is checked
+    /// exactly like in `Map::perform_move`.
+    pub fn predict_move(&self, mv: Move, teams: &Teams) -> Result<MoveOutcome, InvalidMove> {
+        if !self.0.is_valid_index(mv.from) {
+            return Err(InvalidMove::FromInvalidTile);
+        }
+        let dst_idx = self
+            .0
+            .neighbor(mv.from, mv.direction)
+            .ok_or(InvalidMove::ToInvalidTile)?;
+        let mut src = self.0.get(mv.from).borrow().state();
+        let mut dst = self.0.get(dst_idx).borrow().state();
+        if src.owner != Some(mv.player) {
+            return Err(InvalidMove::SourceTileNotOwned);
+        }
+        src.attack(&mut dst, teams)
+    }
+
+    /// Return the shortest path between two tiles, as a list of tile indices, starting with
+    /// `from` and ending with `to`. Only the tiles the given player can see as open are crossed:
+    /// mountains are avoided, and so are the cities that are in the fog of war, since they look
+    /// like mountains to the player. Return `None` if there is no such path.
+    pub fn path(&self, player: PlayerId, from: usize, to: usize) -> Option<Vec<usize>> {
+        if !self.0.is_valid_index(from) || !self.0.is_valid_index(to) {
+            return None;
+        }
+        let is_open = |index: usize| {
+            let tile = self.0.get(index).borrow();
+            !tile.is_mountain() && (!tile.is_city() || tile.is_visible_by(player))
+        };
+
+        // Breadth first search, remembering from which tile each tile was reached.
+        let mut previous: Vec<Option<usize>> = vec![None; self.len()];
+        let mut queue = VecDeque::new();
+        queue.push_back(from);
+        previous[from] = Some(from);
+        while let Some(index) = queue.pop_front() {
+            if index == to {
+                let mut path = vec![to];
+                let mut current = to;
+                while current != from {
+                    current = previous[current].unwrap();
+                    path.push(current);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for neighbor in self.0.direct_neighbors(index) {
+                if previous[neighbor].is_none() && is_open(neighbor) {
+                    previous[neighbor] = Some(index);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        None
+    }
+
+    /// Return the direction to go from a tile to one of its direct neighbors.
+    pub fn direction(&self, from: usize, to: usize) -> Option<Direction> {
+        self.0.direction(from, to)
+    }
+
+    /// Return all the moves the given player can make, with their predicted outcome. A move is
+    /// returned if and only if `Map::perform_move` would accept it.
+    pub fn legal_moves(&self, player: PlayerId, teams: &Teams) -> Vec<(Move, MoveOutcome)> {
+        legal_moves(&self.0, player, teams, |tile| tile.borrow().state())
+    }
+
+    /// Return a copy of the state of all the tiles, see `TileState`.
+    pub fn states(&self) -> Grid<TileState> {
+        self.0.map_tiles(|tile| tile.borrow().state())
+    }
+
+    /// Return an iterator over all the tiles.
+    pub fn iter(&self) -> impl Iterator<Item = Ref<Tile>> {
+        self.0.iter().map(RefCell::borrow)
     }
 
     /// Return an iterator over all the tiles. The tiles are mutable.
@@ -179,19 +297,28 @@ impl Map {
         false
     }
 
+    /// Give all the tiles of the given player back to nobody, with their units. The player's
+    /// generals become cities, and the player cannot see the map anymore.
+    pub fn release(&mut self, player: PlayerId) {
+        for mut tile in self.iter_mut().filter(|t| !t.is_mountain()) {
+            if tile.owner() == Some(player) {
+                tile.set_owner(None);
+                if tile.is_general() {
+                    tile.make_city();
+                }
+            }
+            tile.hide_from(player);
+        }
+    }
+
     /// Increment the number of units of the tiles that are owned by players. If the
     /// `reinforce_all_tiles` is `false`, then only the generals and cityes are reinforced,
-    /// otherwise, all the tiles are reinforced.
-    pub fn reinforce(&mut self, reinforce_all_tiles: bool) {
+    /// otherwise, all the tiles are reinforced. The tiles of the `defeated` players are not
+    /// reinforced.
+    pub fn reinforce(&mut self, reinforce_all_tiles: bool, defeated: &[PlayerId]) {
         for mut tile in self.iter_mut().filter(|t| !t.is_mountain()) {
-            if
-            // reinforce open tiles only when there's a global reinforcement round
-            (tile.owner().is_some() && reinforce_all_tiles)
-                    // reinforce generals every round
-                    || tile.is_general()
-                    // reinfoce city every round if they are occupied
-                    || (tile.is_city() && tile.owner().is_some())
-            {
+            let abandoned = tile.owner().is_some_and(|owner| defeated.contains(&owner));
+            if !abandoned && tile.state().needs_reinforcement(reinforce_all_tiles) {
                 trace!("reinforcing tile {:?}", tile);
                 tile.incr_units(1);
             }
@@ -0,0 +1,293 @@
+//! This module contains code to measure how fair a map is.
+//!
+//! For each general, we compute a few metrics describing its surroundings, like the distance to
+//! the other generals through the tiles that are not mountains, or the number of open tiles that
+//! can be reached in a few moves. A map is fair when these metrics are about the same for all the
+//! generals: the difference between the highest and the lowest value of a metric is its spread,
+//! and `Fairness` gives the maximum spread of each metric.
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use super::common::Tile;
+use super::grid::Grid;
+
+/// Default number of moves within which the surroundings of the generals are measured
+pub const DEFAULT_RADIUS: usize = 8;
+
+/// The metrics of a general.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneralMetrics {
+    /// The general's tile
+    pub tile: usize,
+    /// Number of moves to reach each general, in the order of the generals, or `None` if a
+    /// general cannot be reached.
+    pub distances: Vec<Option<usize>>,
+    /// Number of open tiles that can be reached within the analysis radius
+    pub open_tiles: usize,
+    /// Number of moves to reach each city that can be reached, from the closest one to the
+    /// farthest one.
+    pub city_distances: Vec<usize>,
+    /// Number of choke points within the analysis radius
+    pub choke_points: usize,
+}
+
+impl GeneralMetrics {
+    /// Return the number of moves to reach the closest other general, if any can be reached.
+    pub fn nearest_general(&self) -> Option<usize> {
+        self.distances
+            .iter()
+            .filter_map(|distance| *distance)
+            .filter(|distance| *distance > 0)
+            .min()
+    }
+
+    /// Return the number of moves to reach the closest city, if any can be reached.
+    pub fn nearest_city(&self) -> Option<usize> {
+        self.city_distances.first().cloned()
+    }
+}
+
+/// The metrics of all the generals of a map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapAnalysis {
+    /// Number of moves within which the surroundings of the generals are measured
+    pub radius: usize,
+    /// The metrics of the generals, in the order of the generals
+    pub generals: Vec<GeneralMetrics>,
+    /// The tiles that are on all the paths between two generals: if such a tile was a mountain,
+    /// the generals would be disconnected.
+    pub choke_points: Vec<usize>,
+}
+
+impl MapAnalysis {
+    /// Analyze the grid, with generals on the given tiles.
+    pub fn new(grid: &Grid<RefCell<Tile>>, generals: &[usize], radius: usize) -> Self {
+        let searches: Vec<Search> = generals
+            .iter()
+            .map(|general| Search::new(grid, *general, None))
+            .collect();
+        let choke_points = choke_points(grid, generals, &searches);
+
+        let generals = generals
+            .iter()
+            .zip(&searches)
+            .map(|(general, search)| {
+                let within_radius =
+                    |index: &usize| search.distances[*index].is_some_and(|d| d <= radius);
+                let mut city_distances: Vec<usize> = (0..grid.len())
+                    .filter(|index| grid.get(*index).borrow().is_city())
+                    .filter_map(|index| search.distances[index])
+                    .collect();
+                city_distances.sort();
+                GeneralMetrics {
+                    tile: *general,
+                    distances: generals.iter().map(|g| search.distances[*g]).collect(),
+                    open_tiles: (0..grid.len())
+                        .filter(within_radius)
+                        .filter(|index| grid.get(*index).borrow().is_open())
+                        .count(),
+                    city_distances,
+                    choke_points: choke_points.iter().filter(|c| within_radius(c)).count(),
+                }
+            })
+            .collect();
+
+        MapAnalysis {
+            radius,
+            generals,
+            choke_points,
+        }
+    }
+
+    /// Return the spread of the distance from each general to the closest other one. If a
+    /// general cannot reach any other general, the spread is `usize::MAX`.
+    pub fn distance_spread(&self) -> usize {
+        spread(self.generals.iter().map(GeneralMetrics::nearest_general))
+    }
+
+    /// Return the spread of the number of open tiles around each general.
+    pub fn open_tiles_spread(&self) -> usize {
+        spread(self.generals.iter().map(|general| Some(general.open_tiles)))
+    }
+
+    /// Return the spread of the distance from each general to the closest city. If only some of
+    /// the generals can reach a city, the spread is `usize::MAX`.
+    pub fn city_spread(&self) -> usize {
+        if self.generals.iter().all(|g| g.city_distances.is_empty()) {
+            return 0;
+        }
+        spread(self.generals.iter().map(GeneralMetrics::nearest_city))
+    }
+
+    /// Return the spread of the number of choke points around each general.
+    pub fn choke_points_spread(&self) -> usize {
+        spread(
+            self.generals
+                .iter()
+                .map(|general| Some(general.choke_points)),
+        )
+    }
+
+    /// Return whether all the spreads are within the limits of the given fairness settings.
+    pub fn is_fair(&self, fairness: &Fairness) -> bool {
+        let within = |limit: Option<usize>, spread: usize| limit.is_none_or(|max| spread <= max);
+        within(fairness.max_distance_spread, self.distance_spread())
+            && within(fairness.max_open_tiles_spread, self.open_tiles_spread())
+            && within(fairness.max_city_spread, self.city_spread())
+            && within(fairness.max_choke_points_spread, self.choke_points_spread())
+    }
+}
+
+/// The maximum spread of each metric, for a map to be considered fair. Metrics without a maximum
+/// are not checked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fairness {
+    /// Number of moves within which the surroundings of the generals are measured
+    pub radius: usize,
+    pub max_distance_spread: Option<usize>,
+    pub max_open_tiles_spread: Option<usize>,
+    pub max_city_spread: Option<usize>,
+    pub max_choke_points_spread: Option<usize>,
+}
+
+impl Default for Fairness {
+    fn default() -> Self {
+        Fairness {
+            radius: DEFAULT_RADIUS,
+            max_distance_spread: None,
+            max_open_tiles_spread: None,
+            max_city_spread: None,
+            max_choke_points_spread: None,
+        }
+    }
+}
+
+impl Fairness {
+    /// Parse fairness settings given as comma separated `key=value` pairs, where the keys are
+    /// `radius`, and the metrics: `distance`, `open`, `city` and `choke`. For instance
+    /// `distance=2,open=5`.
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        let mut fairness = Fairness::default();
+        for setting in spec.split(',') {
+            let mut parts = setting.splitn(2, '=');
+            let key = parts.next()?.trim();
+            let value = parts.next()?.trim().parse().ok()?;
+            match key {
+                "radius" => fairness.radius = value,
+                "distance" => fairness.max_distance_spread = Some(value),
+                "open" => fairness.max_open_tiles_spread = Some(value),
+                "city" => fairness.max_city_spread = Some(value),
+                "choke" => fairness.max_choke_points_spread = Some(value),
+                _ => return None,
+            }
+        }
+        Some(fairness)
+    }
+
+    /// Return whether some metric has a maximum spread.
+    pub fn is_constrained(&self) -> bool {
+        self.max_distance_spread.is_some()
+            || self.max_open_tiles_spread.is_some()
+            || self.max_city_spread.is_some()
+            || self.max_choke_points_spread.is_some()
+    }
+}
+
+/// Return the difference between the highest and the lowest value, or `usize::MAX` if some values
+/// are missing.
+fn spread<I: Iterator<Item = Option<usize>>>(values: I) -> usize {
+    let mut min = usize::MAX;
+    let mut max = 0;
+    for value in values {
+        match value {
+            Some(value) => {
+                min = min.min(value);
+                max = max.max(value);
+            }
+            None => return usize::MAX,
+        }
+    }
+    max.saturating_sub(min)
+}
+
+/// A breadth first search from a tile, through the tiles that are not mountains.
+struct Search {
+    distances: Vec<Option<usize>>,
+    previous: Vec<Option<usize>>,
+}
+
+impl Search {
+    /// Search from the given tile. The blocked tile, if any, is considered as a mountain.
+    fn new(grid: &Grid<RefCell<Tile>>, from: usize, blocked: Option<usize>) -> Self {
+        let mut distances = vec![None; grid.len()];
+        let mut previous = vec![None; grid.len()];
+        let mut queue = VecDeque::new();
+        distances[from] = Some(0);
+        queue.push_back(from);
+        while let Some(index) = queue.pop_front() {
+            let distance = distances[index].unwrap() + 1;
+            for neighbor in grid.direct_neighbors(index) {
+                if distances[neighbor].is_some()
+                    || Some(neighbor) == blocked
+                    || grid.get(neighbor).borrow().is_mountain()
+                {
+                    continue;
+                }
+                distances[neighbor] = Some(distance);
+                previous[neighbor] = Some(index);
+                queue.push_back(neighbor);
+            }
+        }
+        Search {
+            distances,
+            previous,
+        }
+    }
+
+    /// Return the tiles of the path found to the given tile, without its ends.
+    fn path_to(&self, to: usize) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut current = self.previous[to];
+        while let Some(index) = current {
+            current = self.previous[index];
+            if current.is_some() {
+                path.push(index);
+            }
+        }
+        path
+    }
+}
+
+/// Return the choke points between the generals, sorted.
+///
+/// A tile that is on all the paths between two generals is on all the paths between the first
+/// general and one of these two generals, so only the tiles of one path from the first general to
+/// each of the others need to be checked.
+fn choke_points(grid: &Grid<RefCell<Tile>>, generals: &[usize], searches: &[Search]) -> Vec<usize> {
+    let first = match searches.first() {
+        Some(search) => search,
+        None => return Vec::new(),
+    };
+    let reachable: Vec<usize> = generals
+        .iter()
+        .cloned()
+        .filter(|general| first.distances[*general].is_some())
+        .collect();
+
+    let mut candidates: Vec<usize> = reachable
+        .iter()
+        .flat_map(|general| first.path_to(*general))
+        .filter(|index| !generals.contains(index))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    candidates
+        .into_iter()
+        .filter(|candidate| {
+            let search = Search::new(grid, generals[0], Some(*candidate));
+            reachable
+                .iter()
+                .any(|general| search.distances[*general].is_none())
+        })
+        .collect()
+}
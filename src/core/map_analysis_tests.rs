@@ -0,0 +1,85 @@
+use super::map_analysis::{Fairness, MapAnalysis};
+use super::map_file::MapFile;
+use super::map_generator::GridBuilder;
+
+fn analyze(text: &str, radius: usize) -> MapAnalysis {
+    let (generals, grid) = MapFile::from_text(text).unwrap().build().unwrap();
+    MapAnalysis::new(&grid, &generals, radius)
+}
+
+#[test]
+fn test_corridor() {
+    let analysis = analyze("S . . . S", 2);
+    assert_eq!(analysis.choke_points, vec![1, 2, 3]);
+    for general in &analysis.generals {
+        assert_eq!(general.nearest_general(), Some(4));
+        assert_eq!(general.open_tiles, 2);
+        assert_eq!(general.choke_points, 2);
+        assert_eq!(general.nearest_city(), None);
+    }
+    assert_eq!(analysis.distance_spread(), 0);
+    assert_eq!(analysis.city_spread(), 0);
+}
+
+#[test]
+fn test_ring() {
+    let analysis = analyze(
+        "
+        S . . . .
+        . # # # .
+        C . . . S
+        ",
+        2,
+    );
+    assert!(analysis.choke_points.is_empty());
+    assert_eq!(analysis.generals[0].distances, vec![Some(0), Some(6)]);
+    assert_eq!(analysis.generals[0].city_distances, vec![2]);
+    assert_eq!(analysis.generals[1].city_distances, vec![4]);
+    assert_eq!(analysis.generals[0].open_tiles, 3);
+    assert_eq!(analysis.generals[1].open_tiles, 4);
+    assert_eq!(analysis.distance_spread(), 0);
+    assert_eq!(analysis.open_tiles_spread(), 1);
+    assert_eq!(analysis.city_spread(), 2);
+    assert_eq!(analysis.choke_points_spread(), 0);
+
+    assert!(analysis.is_fair(&Fairness::default()));
+    assert!(analysis.is_fair(&Fairness::from_spec("city=2,open=1").unwrap()));
+    assert!(!analysis.is_fair(&Fairness::from_spec("city=1").unwrap()));
+    assert!(!analysis.is_fair(&Fairness::from_spec("open=0").unwrap()));
+}
+
+#[test]
+fn test_disconnected() {
+    let (generals, grid) = MapFile::from_text("S . S").unwrap().build().unwrap();
+    grid.get(1).borrow_mut().make_mountain();
+    let analysis = MapAnalysis::new(&grid, &generals, 2);
+    assert_eq!(analysis.generals[0].nearest_general(), None);
+    assert_eq!(analysis.distance_spread(), usize::MAX);
+    assert!(analysis.choke_points.is_empty());
+}
+
+#[test]
+fn test_fairness_spec() {
+    let fairness = Fairness::from_spec("radius=6, distance=2,choke=1").unwrap();
+    assert_eq!(fairness.radius, 6);
+    assert_eq!(fairness.max_distance_spread, Some(2));
+    assert_eq!(fairness.max_open_tiles_spread, None);
+    assert_eq!(fairness.max_choke_points_spread, Some(1));
+    assert!(fairness.is_constrained());
+    assert!(!Fairness::default().is_constrained());
+    assert_eq!(Fairness::from_spec("distance"), None);
+    assert_eq!(Fairness::from_spec("walls=1"), None);
+}
+
+#[test]
+fn test_fair_generation() {
+    let fairness = Fairness::from_spec("open=2,distance=0").unwrap();
+    for seed in 0..5 {
+        let (generals, grid) = GridBuilder::with_seed(2, seed)
+            .fairness(fairness.clone())
+            .build()
+            .unwrap();
+        let analysis = MapAnalysis::new(&grid, &generals, fairness.radius);
+        assert!(analysis.is_fair(&fairness), "seed {}: {:?}", seed, analysis);
+    }
+}
@@ -0,0 +1,330 @@
+//! This module contains code to load hand-made maps from files.
+//!
+//! Maps can be written in two formats. The text format is a grid of cells separated by
+//! whitespace, one line of the map per line of text. Empty lines and lines starting with `;` are
+//! ignored. The cells are:
+//!
+//! - `.`: an open tile
+//! - `#`: a mountain
+//! - `S`: a spawn point, where a general is placed
+//! - `C` or `C<units>`: a city, with the given garrison (`DEFAULT_CITY_UNITS` by default)
+//! - `<units>`: an open tile occupied by a neutral army
+//! - `_`: a void cell, which is out of the map. Void cells give maps arbitrary shapes.
+//!
+//! For instance:
+//!
+//! ```text
+//! ; A small map for two players
+//! S  .  .   #  .
+//! .  #  C30 .  .
+//! .  .  10  #  S
+//! ```
+//!
+//! The JSON format describes the same things with coordinates, as `[column, line]` pairs. All the
+//! tiles that are not listed are open:
+//!
+//! ```json
+//! {
+//!     "name": "small",
+//!     "width": 5,
+//!     "height": 3,
+//!     "mountains": [[3, 0], [1, 1], [3, 2]],
+//!     "cities": [{"tile": [2, 1], "units": 30}],
+//!     "spawns": [[0, 0], [4, 2]],
+//!     "neutral": [{"tile": [2, 2], "units": 10}]
+//! }
+//! ```
+//!
+//! The void cells are listed the same way, under the `"void"` key. With `"topology": "torus"`,
+//! the map wraps around its edges (see `Topology`). Maps in the text format are always flat.
+//!
+//! Files with a `.json` extension are read as JSON, the other ones as text. A map is only valid if
+//! all its spawn points are connected by tiles that are not mountains. The number of spawn points
+//! is the number of players the map is made for. Maps cannot be wider or higher than the biggest
+//! maps generated for this number of players (see `max_grid_size`), so that the views of the map
+//! always fit in the same planes (see `Observation`).
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use fera_unionfind::UnionFindRange;
+use serde_json;
+
+use super::common::Tile;
+use super::grid::{Grid, Topology};
+use super::map_generator::max_grid_size;
+
+/// Number of units in the cities of the text format that do not specify their garrison
+pub const DEFAULT_CITY_UNITS: u16 = 40;
+
+/// A tile occupied by units, given as `[column, line]`.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Garrison {
+    pub tile: (usize, usize),
+    pub units: u16,
+}
+
+/// The description of a map, as read from a file.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct MapFile {
+    /// Name of the map. Maps read from a file without a name are named after the file.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub width: usize,
+    pub height: usize,
+    #[serde(default)]
+    pub mountains: Vec<(usize, usize)>,
+    #[serde(default)]
+    pub cities: Vec<Garrison>,
+    pub spawns: Vec<(usize, usize)>,
+    /// Open tiles occupied by neutral armies
+    #[serde(default)]
+    pub neutral: Vec<Garrison>,
+    /// Cells that are out of the map
+    #[serde(default)]
+    pub void: Vec<(usize, usize)>,
+    #[serde(default)]
+    pub topology: Topology,
+}
+
+impl MapFile {
+    /// Read a map from the given file. The format depends on the file's extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, MapError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        let mut map = if path.extension().is_some_and(|ext| ext == "json") {
+            Self::from_json(&content)?
+        } else {
+            Self::from_text(&content)?
+        };
+        if map.name.is_none() {
+            map.name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned());
+        }
+        Ok(map)
+    }
+
+    /// Parse a map in the JSON format.
+    pub fn from_json(content: &str) -> Result<Self, MapError> {
+        Ok(serde_json::from_str(content)?)
+    }
+
+    /// Parse a map in the text format.
+    pub fn from_text(content: &str) -> Result<Self, MapError> {
+        let mut map = MapFile::default();
+        let lines = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with(';'));
+        for (line, text) in lines.enumerate() {
+            let mut width = 0;
+            for (column, cell) in text.split_whitespace().enumerate() {
+                let tile = (column, line);
+                let invalid = || MapError::InvalidCell(cell.to_string());
+                match cell {
+                    "." => {}
+                    "#" => map.mountains.push(tile),
+                    "_" => map.void.push(tile),
+                    "S" => map.spawns.push(tile),
+                    "C" => map.cities.push(Garrison {
+                        tile,
+                        units: DEFAULT_CITY_UNITS,
+                    }),
+                    _ if cell.starts_with('C') => map.cities.push(Garrison {
+                        tile,
+                        units: cell[1..].parse().map_err(|_| invalid())?,
+                    }),
+                    _ => map.neutral.push(Garrison {
+                        tile,
+                        units: cell.parse().map_err(|_| invalid())?,
+                    }),
+                }
+                width += 1;
+            }
+            if line > 0 && width != map.width {
+                return Err(MapError::InvalidDimensions);
+            }
+            map.width = width;
+            map.height += 1;
+        }
+        Ok(map)
+    }
+
+    /// Return the number of players the map is made for.
+    pub fn nb_players(&self) -> usize {
+        self.spawns.len()
+    }
+
+    /// Build the grid described by the map, and return it with the generals' positions, in the
+    /// order of the spawn points. Return an error if the map is not valid.
+    pub fn build(&self) -> Result<(Vec<usize>, Grid<RefCell<Tile>>), MapError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(MapError::InvalidDimensions);
+        }
+        if self.spawns.is_empty() {
+            return Err(MapError::NoSpawn);
+        }
+        let max_size = max_grid_size(self.spawns.len());
+        if self.width > max_size || self.height > max_size {
+            return Err(MapError::TooBig(max_size));
+        }
+        let mut grid = Grid::new(
+            |_| {
+                let mut tile = Tile::new();
+                tile.make_open();
+                RefCell::new(tile)
+            },
+            self.width,
+            self.height,
+        );
+        grid.set_topology(self.topology);
+
+        // Each tile can only be described once.
+        let mut described = vec![false; grid.len()];
+        let mut describe = |(column, line): (usize, usize)| {
+            if column >= self.width || line >= self.height {
+                return Err(MapError::OutOfBounds(column, line));
+            }
+            let index = line * self.width + column;
+            if described[index] {
+                return Err(MapError::Overlap(column, line));
+            }
+            described[index] = true;
+            Ok(index)
+        };
+        // Void cells are mountains that are not the neighbors of any tile.
+        for cell in &self.void {
+            let index = describe(*cell)?;
+            grid.get(index).borrow_mut().make_mountain();
+            grid.make_void(index);
+        }
+        for tile in &self.mountains {
+            grid.get(describe(*tile)?).borrow_mut().make_mountain();
+        }
+        for city in &self.cities {
+            let mut tile = grid.get(describe(city.tile)?).borrow_mut();
+            tile.make_city();
+            tile.set_units(city.units);
+        }
+        for army in &self.neutral {
+            grid.get(describe(army.tile)?)
+                .borrow_mut()
+                .set_units(army.units);
+        }
+        let mut generals = Vec::with_capacity(self.spawns.len());
+        for spawn in &self.spawns {
+            let index = describe(*spawn)?;
+            grid.get(index).borrow_mut().make_general();
+            generals.push(index);
+        }
+
+        // Check that all the spawn points are connected to the first one.
+        let mut uf = UnionFindRange::with_keys_in_range(..grid.len());
+        for index in 0..grid.len() {
+            if grid.get(index).borrow().is_mountain() {
+                continue;
+            }
+            for neighbor in grid.direct_neighbors(index) {
+                if !grid.get(neighbor).borrow().is_mountain() && !uf.in_same_set(index, neighbor) {
+                    uf.union(index, neighbor);
+                }
+            }
+        }
+        if generals.iter().any(|g| !uf.in_same_set(generals[0], *g)) {
+            return Err(MapError::Disconnected);
+        }
+        Ok((generals, grid))
+    }
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// A cell of the text format is not valid.
+    InvalidCell(String),
+    /// The map is empty, or the lines of the text format do not have the same width.
+    InvalidDimensions,
+    /// A tile, given as `(column, line)`, is out of the map.
+    OutOfBounds(usize, usize),
+    /// A tile, given as `(column, line)`, is described more than once.
+    Overlap(usize, usize),
+    /// The map has no spawn point.
+    NoSpawn,
+    /// The map is not made for the number of players of the game. This is the number of players
+    /// of the map.
+    IncompatiblePlayers(usize),
+    /// Some spawn points are not connected to the others.
+    Disconnected,
+    /// The map is wider or higher than the given maximum size.
+    TooBig(usize),
+}
+
+impl From<io::Error> for MapError {
+    fn from(err: io::Error) -> Self {
+        MapError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for MapError {
+    fn from(err: serde_json::Error) -> Self {
+        MapError::Json(err)
+    }
+}
+
+impl Error for MapError {
+    fn description(&self) -> &str {
+        match *self {
+            MapError::Io(ref e) => e.description(),
+            MapError::Json(ref e) => e.description(),
+            MapError::InvalidCell(_) => "invalid cell",
+            MapError::InvalidDimensions => "the map is empty or not rectangular",
+            MapError::OutOfBounds(..) => "tile out of the map",
+            MapError::Overlap(..) => "tile described more than once",
+            MapError::NoSpawn => "the map has no spawn point",
+            MapError::IncompatiblePlayers(_) => "the map is not made for this number of players",
+            MapError::Disconnected => "the spawn points are not all connected",
+            MapError::TooBig(_) => "the map is too big for its number of players",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            MapError::Io(ref e) => Some(e),
+            MapError::Json(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::Io(e) => write!(f, "Map error (io): {}", e),
+            MapError::Json(e) => write!(f, "Map error (json): {}", e),
+            MapError::InvalidCell(cell) => write!(f, "Map error: {}: {}", self.description(), cell),
+            MapError::OutOfBounds(column, line) | MapError::Overlap(column, line) => write!(
+                f,
+                "Map error: {}: ({}, {})",
+                self.description(),
+                column,
+                line
+            ),
+            MapError::IncompatiblePlayers(nb_players) => {
+                write!(f, "Map error: the map is made for {} players", nb_players)
+            }
+            MapError::TooBig(max) => write!(
+                f,
+                "Map error: {} (at most {}x{})",
+                self.description(),
+                max,
+                max
+            ),
+            _ => write!(f, "Map error: {}", self.description()),
+        }
+    }
+}
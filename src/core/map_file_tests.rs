@@ -0,0 +1,174 @@
+use super::game::Game;
+use super::grid::Topology;
+use super::map::Map;
+use super::map_file::{Garrison, MapError, MapFile};
+use super::map_generator::max_grid_size;
+
+const TEXT_MAP: &str = "
+; A small map for two players
+S  .  .   #  .
+.  #  C30 .  .
+.  .  10  #  S
+";
+
+const JSON_MAP: &str = r#"{
+    "width": 5,
+    "height": 3,
+    "mountains": [[3, 0], [1, 1], [3, 2]],
+    "cities": [{"tile": [2, 1], "units": 30}],
+    "spawns": [[0, 0], [4, 2]],
+    "neutral": [{"tile": [2, 2], "units": 10}]
+}"#;
+
+#[test]
+fn test_parse_text() {
+    let map = MapFile::from_text(TEXT_MAP).unwrap();
+    assert_eq!((map.width, map.height), (5, 3));
+    assert_eq!(map.mountains, vec![(3, 0), (1, 1), (3, 2)]);
+    assert_eq!(
+        map.cities,
+        vec![Garrison {
+            tile: (2, 1),
+            units: 30
+        }]
+    );
+    assert_eq!(map.spawns, vec![(0, 0), (4, 2)]);
+    assert_eq!(
+        map.neutral,
+        vec![Garrison {
+            tile: (2, 2),
+            units: 10
+        }]
+    );
+    assert_eq!(map, MapFile::from_json(JSON_MAP).unwrap());
+}
+
+#[test]
+fn test_build() {
+    let (generals, map) = Map::from_file(&MapFile::from_text(TEXT_MAP).unwrap()).unwrap();
+    assert_eq!(generals, vec![0, 14]);
+    assert_eq!((map.width(), map.height()), (5, 3));
+    assert!(map.get(0).is_general());
+    assert!(map.get(3).is_mountain());
+    assert!(map.get(7).is_city());
+    assert_eq!(map.get(7).units(), 30);
+    assert!(map.get(12).is_open());
+    assert_eq!(map.get(12).units(), 10);
+    assert_eq!(map.get(12).owner(), None);
+
+    let game = Game::with_map_file(vec![0, 1], &MapFile::from_text(TEXT_MAP).unwrap()).unwrap();
+    assert_eq!(game.map.get(14).owner(), Some(1));
+}
+
+#[test]
+fn test_void() {
+    let map = MapFile::from_text(
+        "
+        S . _
+        # . .
+        _ . S
+        ",
+    )
+    .unwrap();
+    assert_eq!(map.void, vec![(2, 0), (0, 2)]);
+    let (_, built) = Map::from_file(&map).unwrap();
+    assert!(built.is_void(2) && built.is_void(6));
+    assert!(built.get(2).is_mountain());
+    assert!(!built.is_void(3));
+
+    // The void cells are sent apart, in the first update only.
+    let mut game = Game::with_map_file(vec![0, 1], &map).unwrap();
+    let update = game.get_update();
+    assert_eq!(update.void(), &[2, 6]);
+    assert_eq!(update.tiles().len(), 7);
+    assert!(update
+        .tiles()
+        .iter()
+        .all(|(index, _)| *index != 2 && *index != 6));
+    game.incr_turn();
+    assert!(game.get_update().void().is_empty());
+}
+
+#[test]
+fn test_torus() {
+    let json = |topology: &str| {
+        format!(
+            r#"{{
+                "width": 5,
+                "height": 1,
+                "mountains": [[2, 0]],
+                "spawns": [[0, 0], [4, 0]],
+                "topology": "{}"
+            }}"#,
+            topology
+        )
+    };
+    // The spawn points are only connected across the edges.
+    let map = MapFile::from_json(&json("flat")).unwrap();
+    assert_eq!(map.topology, Topology::Flat);
+    match map.build() {
+        Err(MapError::Disconnected) => {}
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+    let map = MapFile::from_json(&json("torus")).unwrap();
+    let mut game = Game::with_map_file(vec![0, 1], &map).unwrap();
+    assert_eq!(game.get_update().topology(), Topology::Torus);
+    assert_eq!(
+        MapFile::from_text("S . S").unwrap().topology,
+        Topology::Flat
+    );
+}
+
+#[test]
+fn test_invalid_maps() {
+    let build = |text: &str| MapFile::from_text(text).and_then(|map| map.build().map(|_| ()));
+    match build("S . # S") {
+        Err(MapError::Disconnected) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+    match build("S . .\n. S") {
+        Err(MapError::InvalidDimensions) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+    match build("S . X") {
+        Err(MapError::InvalidCell(ref cell)) if cell == "X" => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+    match build("S _ S") {
+        Err(MapError::Disconnected) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+    match build(". . .") {
+        Err(MapError::NoSpawn) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+
+    let mut map = MapFile::from_text("S . S").unwrap();
+    map.mountains.push((0, 0));
+    match map.build() {
+        Err(MapError::Overlap(0, 0)) => {}
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+    map.mountains = vec![(3, 0)];
+    match map.build() {
+        Err(MapError::OutOfBounds(3, 0)) => {}
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+    map.mountains.clear();
+    map.width = max_grid_size(2) + 1;
+    match map.build() {
+        Err(MapError::TooBig(max)) if max == max_grid_size(2) => {}
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+    match Game::with_map_file(vec![0, 1, 2], &MapFile::from_text("S . S").unwrap()) {
+        Err(MapError::IncompatiblePlayers(2)) => {}
+        _ => panic!("the map should not be compatible with 3 players"),
+    }
+}
+
+#[test]
+fn test_bundled_maps() {
+    let map = MapFile::from_text(include_str!("../../maps/crossroads.txt")).unwrap();
+    assert_eq!(map.nb_players(), 2);
+    assert!(map.build().is_ok());
+}
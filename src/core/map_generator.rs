@@ -1,35 +1,261 @@
 //! This module contains code to generate random grids.
 //!
-//! Grids have variable sizes, based on the number of players. Generals are spawned randomly, but
-//! with a minimum manhattan distance between each other.
+//! Grids have variable sizes, based on the number of players. Generals are spawned first, as far
+//! as possible from each other, and with a minimum manhattan distance between each other: if they
+//! don't fit on the grid, or if void cells prevent connecting them, an error is returned.
 //!
 //! Finally, the topologies are random, but there is a least one open path between the generals.
+//! They are noisy, cave-like, maze-like or made of lanes, depending on the `Strategy`.
+//!
+//! Grids are rectangular, or have another `Shape`: the cells out of the shape are void. They can
+//! also wrap around their edges, depending on their `Topology`.
+//!
+//! Grids can also be symmetric, so that no player is favored by the map: half of the grid is
+//! generated, and the other half is its image (see `Symmetry`).
+//!
+//! Grids that are not fair enough according to `Fairness` are generated again, up to
+//! `MAX_ATTEMPTS` times.
 use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
 
 use fera_unionfind::UnionFindRange;
-use rand::{rngs::ThreadRng, thread_rng, Rng};
+use rand::{rngs::StdRng, seq::SliceRandom, FromEntropy, Rng, SeedableRng};
 
 use super::common::Tile;
-use super::grid::Grid;
+use super::grid::{Grid, Topology};
+use super::map_analysis::{Fairness, MapAnalysis};
+use super::map_file::DEFAULT_CITY_UNITS;
 
 const MIN_DISTANCE: usize = 10;
+/// Minimum distance between the generals of toroidal grids, where distances are shorter since the
+/// generals cannot be spawned in the corners
+const TORUS_MIN_DISTANCE: usize = 8;
 const MIN_GRID_SIZE: usize = 17;
 const GRID_SIZE_MAX_DELTA: usize = 6;
+/// Maximum number of grids generated to find a fair one. The last one is used even if it's not
+/// fair.
+pub const MAX_ATTEMPTS: usize = 100;
+/// Maximum number of grids on which the generals do not fit or cannot be connected, before giving
+/// up
+pub const MAX_SPAWN_FAILURES: usize = 10;
+/// Default proportion of open tiles of the grids generated with the random strategy
+pub const DEFAULT_RANDOM_DENSITY: f64 = 0.55;
+/// Default proportion of tiles that are closed before the caves are smoothed
+pub const DEFAULT_CAVE_DENSITY: f64 = 0.45;
+/// Number of steps of the cellular automaton that smoothes the caves
+const CAVE_SMOOTHING_STEPS: usize = 4;
+/// Probability to open a wall at each dead end of a maze
+const MAZE_LOOP_PROBABILITY: f64 = 0.3;
+/// Default number of lanes of lane grids
+pub const DEFAULT_LANES: usize = 3;
+/// Default width of the lanes of lane grids
+pub const DEFAULT_LANE_WIDTH: usize = 2;
+/// Number of columns of the bases on each side of lane grids
+const LANE_BASE_WIDTH: usize = 3;
+
+/// Return the maximum width and height of the grids generated for the given number of generals.
+pub fn max_grid_size(nb_generals: usize) -> usize {
+    MIN_GRID_SIZE + nb_generals + GRID_SIZE_MAX_DELTA
+}
+
+/// The symmetry of a generated grid. With a symmetry, each tile that is opened also opens its
+/// images, and each general is spawned with its images, so that all the players get the same
+/// surroundings and the same paths to each other.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Symmetry {
+    /// The grid is fully random.
+    #[default]
+    None,
+    /// The grid is symmetric with respect to its center. For 2 players.
+    Point,
+    /// The left half of the grid is the mirror of the right half. For 2 players.
+    Mirror,
+    /// The grid is square, and it's unchanged when rotated by a quarter turn. For 4 players.
+    Rotational,
+}
+
+impl Symmetry {
+    /// Return the symmetry with the given name: `none`, `point`, `mirror` or `rotational`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Symmetry::None),
+            "point" => Some(Symmetry::Point),
+            "mirror" => Some(Symmetry::Mirror),
+            "rotational" => Some(Symmetry::Rotational),
+            _ => None,
+        }
+    }
+
+    /// Return whether grids with this symmetry can be generated for the given number of generals.
+    pub fn supports(self, nb_generals: usize) -> bool {
+        match self {
+            Symmetry::None => true,
+            Symmetry::Point | Symmetry::Mirror => nb_generals == 2,
+            Symmetry::Rotational => nb_generals == 4,
+        }
+    }
+}
+
+/// How the tiles of a grid are opened.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Strategy {
+    /// Carve random paths between the generals, and open random tiles until the given proportion
+    /// of the tiles is open. This gives noisy grids.
+    Random { density: f64 },
+    /// Dig cave-like regions and corridors with a cellular automaton. The density is the
+    /// proportion of tiles that are closed before the caves are smoothed: the higher it is, the
+    /// smaller the caves.
+    Cave { density: f64 },
+    /// Dig a maze of corridors of the given width, with a few loops.
+    Maze { width: usize },
+    /// Dig parallel lanes of the given width between the generals' bases, on opposite sides of
+    /// the grid. Mountain walls separate the lanes, that meet in the middle around clusters of
+    /// cities.
+    Lanes { lanes: usize, width: usize },
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Strategy::Random {
+            density: DEFAULT_RANDOM_DENSITY,
+        }
+    }
+}
+
+impl Strategy {
+    /// Parse a strategy: `random` or `cave` with an optional density, like `random:0.6` or
+    /// `cave:0.4`, `maze` with an optional corridor width, like `maze:2`, or `lanes` with an
+    /// optional number of lanes and lane width, like `lanes:3` or `lanes:3:2`.
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        let mut parts = spec.split(':');
+        let name = parts.next()?;
+        let params: Vec<&str> = parts.collect();
+        let size = |param: &str| param.parse().ok().filter(|size| *size > 0);
+        match (name, params.as_slice()) {
+            ("random", []) => Some(Strategy::default()),
+            ("random", [density]) => match density.parse() {
+                Ok(density) if density > 0.0 && density <= 1.0 => {
+                    Some(Strategy::Random { density })
+                }
+                _ => None,
+            },
+            ("cave", []) => Some(Strategy::Cave {
+                density: DEFAULT_CAVE_DENSITY,
+            }),
+            ("cave", [density]) => match density.parse() {
+                Ok(density) if (0.0..1.0).contains(&density) => Some(Strategy::Cave { density }),
+                _ => None,
+            },
+            ("maze", []) => Some(Strategy::Maze { width: 1 }),
+            ("maze", [width]) => Some(Strategy::Maze {
+                width: size(width)?,
+            }),
+            ("lanes", []) => Some(Strategy::Lanes {
+                lanes: DEFAULT_LANES,
+                width: DEFAULT_LANE_WIDTH,
+            }),
+            ("lanes", [lanes]) => Some(Strategy::Lanes {
+                lanes: size(lanes)?,
+                width: DEFAULT_LANE_WIDTH,
+            }),
+            ("lanes", [lanes, width]) => Some(Strategy::Lanes {
+                lanes: size(lanes)?,
+                width: size(width)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The shape of a grid. The cells out of the shape are void. All the shapes are centered, so they
+/// do not break the symmetry of the grid.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Shape {
+    /// The whole grid.
+    #[default]
+    Rectangle,
+    /// An ellipse that touches the sides of the grid.
+    Island,
+    /// An island with a void lake in the middle, half as wide and high as the island.
+    Ring,
+    /// A horizontal and a vertical band that cross in the middle, each half as wide as the grid.
+    Cross,
+}
+
+impl Shape {
+    /// Return the shape with the given name: `rectangle`, `island`, `ring` or `cross`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rectangle" => Some(Shape::Rectangle),
+            "island" => Some(Shape::Island),
+            "ring" => Some(Shape::Ring),
+            "cross" => Some(Shape::Cross),
+            _ => None,
+        }
+    }
+
+    /// Return whether the cell at the given column and line of a grid of the given dimensions is
+    /// in the shape.
+    fn contains(self, column: usize, line: usize, width: usize, height: usize) -> bool {
+        // Coordinates of the center of the cell, relative to the center of the grid, and doubled
+        // so that they are integers.
+        let x = (2 * column + 1) as i64 - width as i64;
+        let y = (2 * line + 1) as i64 - height as i64;
+        let (width, height) = (width as i64, height as i64);
+        // The cell is in the ellipse of the grid's dimensions scaled by `scale` if
+        // (x / width)^2 + (y / height)^2 <= scale^2.
+        let in_ellipse = |scale: i64| {
+            scale * scale * (x * x * height * height + y * y * width * width)
+                <= width * width * height * height
+        };
+        match self {
+            Shape::Rectangle => true,
+            Shape::Island => in_ellipse(1),
+            Shape::Ring => in_ellipse(1) && !in_ellipse(2),
+            Shape::Cross => 2 * x.abs() <= width || 2 * y.abs() <= height,
+        }
+    }
+}
+
+/// The settings of the random grids.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GeneratorConfig {
+    pub strategy: Strategy,
+    pub shape: Shape,
+    pub topology: Topology,
+    pub symmetry: Symmetry,
+    pub fairness: Fairness,
+}
 
 /// A temporary datastructure used to generate a random grid.
 #[derive(Debug)]
 pub struct GridBuilder {
-    grid: Grid<RefCell<Tile>>,
-    rng: ThreadRng,
-    generals: Vec<usize>,
+    pub(crate) grid: Grid<RefCell<Tile>>,
+    rng: StdRng,
+    pub(crate) generals: Vec<usize>,
     nb_generals: usize,
+    strategy: Strategy,
+    shape: Shape,
+    topology: Topology,
+    symmetry: Symmetry,
+    fairness: Fairness,
 }
 
 impl GridBuilder {
     /// Return a new builder. The grid dimensions are random but are related to the number of
     /// generals: more generals mean bigger grid.
     pub fn new(nb_generals: usize) -> Self {
-        let mut rng = thread_rng();
+        Self::with_rng(nb_generals, StdRng::from_entropy())
+    }
+
+    /// Return a new builder that always generates the same grid for a given seed.
+    pub fn with_seed(nb_generals: usize, seed: u64) -> Self {
+        Self::with_rng(nb_generals, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(nb_generals: usize, mut rng: StdRng) -> Self {
         let width = MIN_GRID_SIZE + nb_generals + rng.gen_range(0, GRID_SIZE_MAX_DELTA + 1);
         let height = MIN_GRID_SIZE + nb_generals + rng.gen_range(0, GRID_SIZE_MAX_DELTA + 1);
 
@@ -38,7 +264,92 @@ impl GridBuilder {
             grid: Grid::new(|_| RefCell::new(Tile::new()), width, height),
             rng,
             nb_generals,
+            strategy: Strategy::default(),
+            shape: Shape::default(),
+            topology: Topology::default(),
+            symmetry: Symmetry::None,
+            fairness: Fairness::default(),
+        }
+    }
+
+    /// Return the builder, set to generate grids with the given settings.
+    pub fn config(self, config: &GeneratorConfig) -> Self {
+        self.strategy(config.strategy)
+            .shape(config.shape)
+            .topology(config.topology)
+            .symmetry(config.symmetry)
+            .fairness(config.fairness.clone())
+    }
+
+    /// Return the builder, set to generate grids with the given strategy.
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Return the builder, set to generate grids with the given shape.
+    pub fn shape(mut self, shape: Shape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Return the builder, set to generate grids with the given topology.
+    pub fn topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Return the builder, set to only generate grids that are fair according to the given
+    /// settings.
+    pub fn fairness(mut self, fairness: Fairness) -> Self {
+        self.fairness = fairness;
+        self
+    }
+
+    /// Return the builder, set to generate a grid with the given symmetry. If the symmetry is not
+    /// made for the number of generals, the grid is not symmetric.
+    pub fn symmetry(mut self, symmetry: Symmetry) -> Self {
+        if !symmetry.supports(self.nb_generals) {
+            warn!(
+                "{:?} symmetry is not supported for {} generals",
+                symmetry, self.nb_generals
+            );
+            return self;
         }
+        if symmetry == Symmetry::Rotational {
+            let size = self.grid.width();
+            self.grid = Grid::new(|_| RefCell::new(Tile::new()), size, size);
+        }
+        self.symmetry = symmetry;
+        self
+    }
+
+    /// Return the given tile and its images by the grid's symmetry, without duplicates.
+    fn orbit(&self, index: usize) -> Vec<usize> {
+        let (width, height) = (self.grid.width(), self.grid.height());
+        let (column, line) = (index % width, index / width);
+        let mut orbit = vec![index];
+        match self.symmetry {
+            Symmetry::None => {}
+            Symmetry::Point => orbit.push((height - 1 - line) * width + width - 1 - column),
+            Symmetry::Mirror => orbit.push(line * width + width - 1 - column),
+            Symmetry::Rotational => {
+                let (mut column, mut line) = (column, line);
+                for _ in 0..3 {
+                    let rotated = (width - 1 - line, column);
+                    column = rotated.0;
+                    line = rotated.1;
+                    orbit.push(line * width + column);
+                }
+            }
+        }
+        let mut unique = Vec::with_capacity(orbit.len());
+        for i in orbit {
+            if !unique.contains(&i) {
+                unique.push(i);
+            }
+        }
+        unique
     }
 
     /// Return whether a given cell on the grid is open (ie is not a mountain or a city).
@@ -47,62 +358,534 @@ impl GridBuilder {
         tile.is_open() || tile.is_general()
     }
 
-    /// Create a new grid with only closed tiles and the generals. Then, keep opening tiles until
-    /// all the generals are connected. Finally, the grid.
-    pub fn build(mut self) -> (Vec<usize>, Grid<RefCell<Tile>>) {
-        let nb_tiles = self.grid.len();
-        let mut uf = UnionFindRange::with_keys_in_range(..nb_tiles);
+    /// Generate grids until one is fair, and return it with the generals' positions. Return an
+    /// error if the generals do not fit on the grid, or cannot be connected.
+    pub fn build(mut self) -> Result<(Vec<usize>, Grid<RefCell<Tile>>), GeneratorError> {
+        let mut nb_failures = 0;
+        for attempt in 1..=MAX_ATTEMPTS {
+            if attempt > 1 {
+                let (width, height) = (self.grid.width(), self.grid.height());
+                self.grid = Grid::new(|_| RefCell::new(Tile::new()), width, height);
+                self.generals.clear();
+            }
+            // The generals are spawned one after the other, so whether they all fit depends on
+            // where the first ones are spawned.
+            if let Err(e) = self.generate() {
+                nb_failures += 1;
+                if nb_failures == MAX_SPAWN_FAILURES || attempt == MAX_ATTEMPTS {
+                    return Err(e);
+                }
+                debug!("{}, generating another grid", e);
+                continue;
+            }
+            if !self.fairness.is_constrained() {
+                break;
+            }
+            let analysis = MapAnalysis::new(&self.grid, &self.generals, self.fairness.radius);
+            if analysis.is_fair(&self.fairness) {
+                debug!("found a fair grid after {} attempts", attempt);
+                break;
+            }
+            if attempt == MAX_ATTEMPTS {
+                warn!("no fair grid after {} attempts: {:?}", attempt, analysis);
+                break;
+            }
+            debug!("grid is not fair, generating another one: {:?}", analysis);
+        }
+        Ok((self.generals, self.grid))
+    }
 
+    /// Generate a new grid, with the builder's strategy.
+    fn generate(&mut self) -> Result<(), GeneratorError> {
         debug!("generating grid");
-        'outer: loop {
-            // Pick a random tile
-            let index = self.rng.gen_range(0, nb_tiles);
-            // If that tile is already open, ignore it and pick another one
+        self.grid.set_topology(self.topology);
+        self.carve_shape();
+        match self.strategy {
+            Strategy::Random { density } => self.open_random_tiles(density)?,
+            Strategy::Cave { density } => self.dig_caves(density)?,
+            Strategy::Maze { width } => self.dig_maze(width)?,
+            Strategy::Lanes { lanes, width } => self.dig_lanes(lanes, width)?,
+        }
+        debug!("successfully generated a grid");
+        // For debugging print the generated grid
+        for (idx, tile) in self.grid.tiles().iter().enumerate() {
+            debug!("index {}: {:?}", idx, tile);
+        }
+        Ok(())
+    }
+
+    /// Make the cells that are out of the builder's shape void.
+    fn carve_shape(&mut self) {
+        let (width, height) = (self.grid.width(), self.grid.height());
+        for index in 0..self.grid.len() {
+            if !self
+                .shape
+                .contains(index % width, index / width, width, height)
             {
-                let tile = self.grid.get(index).borrow();
-                if tile.is_open() || tile.is_general() {
-                    continue;
+                self.grid.make_void(index);
+            }
+        }
+    }
+
+    /// Open the given tile and its images, and connect them to their neighbors that are already
+    /// open. Return the opened tiles. Void cells are never opened.
+    fn open(&self, index: usize, uf: &mut UnionFindRange) -> Vec<usize> {
+        let mut orbit = self.orbit(index);
+        orbit.retain(|index| !self.grid.is_void(*index));
+        for index in &orbit {
+            self.grid.get(*index).borrow_mut().make_open();
+            for i in self.grid.direct_neighbors(*index) {
+                if self.is_open(i) && !uf.in_same_set(*index, i) {
+                    uf.union(*index, i);
+                }
+            }
+        }
+        orbit
+    }
+
+    /// Return the tiles where generals can be spawned, given the tile picked for the next one and
+    /// its images: they must be far enough from all the other generals. On symmetric grids, all
+    /// the generals are spawned at once, on the tile and its images, which must also be far enough
+    /// from each other.
+    fn spawns(&self, index: usize, orbit: Vec<usize>) -> Option<Vec<usize>> {
+        let spawns = if self.symmetry == Symmetry::None {
+            vec![index]
+        } else if orbit.len() == self.nb_generals {
+            orbit
+        } else {
+            return None;
+        };
+        let min_distance = match self.topology {
+            Topology::Flat => MIN_DISTANCE,
+            Topology::Torus => TORUS_MIN_DISTANCE,
+        };
+        for (i, spawn) in spawns.iter().enumerate() {
+            for general in self.generals.iter().chain(&spawns[..i]) {
+                if self.grid.manhattan_distance(*spawn, *general) < min_distance {
+                    return None;
                 }
             }
-            // Open the tile, and connect it to its neighbors that are already open
-            self.grid.get(index).borrow_mut().make_open();
-            for i in self.grid.direct_neighbors(index) {
-                if self.is_open(i) && !uf.in_same_set(index, i) {
-                    uf.union(index, i);
+        }
+        Some(spawns)
+    }
+
+    fn spawn_generals(&mut self, spawns: Vec<usize>) {
+        for spawn in spawns {
+            info!("making {} a general", spawn);
+            self.grid.get(spawn).borrow_mut().make_general();
+            self.generals.push(spawn);
+        }
+    }
+
+    /// Start from a grid with only closed tiles, and spawn the generals. Then, carve random paths
+    /// between the generals, and open random tiles until the given proportion of the tiles is
+    /// open.
+    fn open_random_tiles(&mut self, density: f64) -> Result<(), GeneratorError> {
+        let nb_tiles = self.grid.len();
+        let mut uf = UnionFindRange::with_keys_in_range(..nb_tiles);
+        self.spawn_generals_far_apart(&mut uf, false)?;
+        for i in 1..self.generals.len() {
+            if !uf.in_same_set(self.generals[0], self.generals[i]) {
+                self.carve_path(self.generals[i], self.generals[0], &mut uf);
+            }
+        }
+        // The paths do not go through void cells, which can leave gaps.
+        self.connect_generals(&mut uf)?;
+
+        let target = (density * self.grid.nb_playable() as f64) as usize;
+        let mut nb_open = (0..nb_tiles).filter(|index| self.is_open(*index)).count();
+        let mut closed: Vec<usize> = (0..nb_tiles)
+            .filter(|index| !self.is_open(*index) && !self.grid.is_void(*index))
+            .collect();
+        closed.shuffle(&mut self.rng);
+        for index in closed {
+            if nb_open >= target {
+                break;
+            }
+            // The tile may have been opened as the image of another one.
+            if !self.is_open(index) {
+                nb_open += self.open(index, &mut uf).len();
+            }
+        }
+        Ok(())
+    }
+
+    /// Open a random path between two tiles, that only gets closer to the destination at each
+    /// step.
+    fn carve_path(&mut self, from: usize, to: usize, uf: &mut UnionFindRange) {
+        let width = self.grid.width();
+        let (mut column, mut line) = (from % width, from / width);
+        let (to_column, to_line) = (to % width, to / width);
+        while (column, line) != (to_column, to_line) {
+            let horizontal = if column == to_column {
+                false
+            } else if line == to_line {
+                true
+            } else {
+                self.rng.gen_bool(0.5)
+            };
+            if horizontal {
+                column = if column < to_column {
+                    column + 1
+                } else {
+                    column - 1
+                };
+            } else {
+                line = if line < to_line { line + 1 } else { line - 1 };
+            }
+            let index = line * width + column;
+            if !self.is_open(index) {
+                let _ = self.open(index, uf);
+            }
+        }
+    }
+
+    /// Dig caves in a grid with only closed tiles: each tile is first closed with the given
+    /// probability, and a cellular automaton then smoothes the grid, closing the tiles that are
+    /// surrounded by closed tiles and opening the others. Generals are spawned on open tiles, and
+    /// tunnels are dug between the caves that are not connected to the first general.
+    fn dig_caves(&mut self, density: f64) -> Result<(), GeneratorError> {
+        let nb_tiles = self.grid.len();
+        // Close random tiles, along with their images.
+        let mut closed = vec![false; nb_tiles];
+        for index in 0..nb_tiles {
+            let orbit = self.orbit(index);
+            if orbit.iter().all(|i| *i >= index) {
+                let is_closed = self.rng.gen_bool(density);
+                for i in orbit {
+                    closed[i] = is_closed;
                 }
             }
+        }
 
-            // If not all the generals have been spawned, and if the tile is far enough from all
-            // the other generals, make it a general.
-            if self.generals.len() < self.nb_generals {
-                for general in &self.generals {
-                    if self.grid.manhattan_distance(index, *general) < MIN_DISTANCE {
-                        continue 'outer;
+        // Smooth the grid. Tiles out of the grid count as closed.
+        for _ in 0..CAVE_SMOOTHING_STEPS {
+            closed = (0..nb_tiles)
+                .map(|index| {
+                    let neighbors: Vec<usize> = self.grid.extended_neighbors(index).collect();
+                    let nb_closed =
+                        8 - neighbors.len() + neighbors.iter().filter(|i| closed[**i]).count();
+                    match nb_closed {
+                        0..=3 => false,
+                        4 => closed[index],
+                        _ => true,
                     }
+                })
+                .collect();
+        }
+
+        let mut uf = self.open_all(&closed);
+        self.spawn_generals_far_apart(&mut uf, true)?;
+        self.connect_generals(&mut uf)?;
+        Ok(())
+    }
+
+    /// Dig a maze of corridors of the given width: the grid is split in square cells separated by
+    /// walls, and a random depth first search opens the walls between the cells, so that there is
+    /// a path between any two cells. Some more walls are then opened, so that the maze has loops.
+    /// Corridors that are too wide for the grid to have 2x2 cells are narrowed.
+    fn dig_maze(&mut self, width: usize) -> Result<(), GeneratorError> {
+        let (grid_width, grid_height) = (self.grid.width(), self.grid.height());
+        let max_width = grid_width.min(grid_height).saturating_sub(1) / 2;
+        let width = width.clamp(1, max_width.max(1));
+        let step = width + 1;
+        let columns = (grid_width + 1) / step;
+        let lines = (grid_height + 1) / step;
+        let mut closed = vec![true; self.grid.len()];
+        // Open the tiles of a cell, or of the wall between two neighbor cells.
+        let mut dig = |column: usize, line: usize, to: (usize, usize)| {
+            let (from_x, from_y) = (column * step, line * step);
+            let (to_x, to_y) = (to.0 * step + width, to.1 * step + width);
+            for y in from_y..to_y {
+                for x in from_x..to_x {
+                    closed[y * grid_width + x] = false;
                 }
-                info!("making {} a general", index);
-                self.grid.get(index).borrow_mut().make_general();
-                self.generals.push(index);
+            }
+        };
+
+        let mut visited = vec![false; columns * lines];
+        let mut stack = vec![(0, 0)];
+        visited[0] = true;
+        dig(0, 0, (0, 0));
+        while let Some(&(column, line)) = stack.last() {
+            let mut neighbors = Vec::with_capacity(4);
+            if column > 0 {
+                neighbors.push((column - 1, line));
+            }
+            if column + 1 < columns {
+                neighbors.push((column + 1, line));
+            }
+            if line > 0 {
+                neighbors.push((column, line - 1));
+            }
+            if line + 1 < lines {
+                neighbors.push((column, line + 1));
+            }
+            let unvisited: Vec<(usize, usize)> = neighbors
+                .iter()
+                .cloned()
+                .filter(|(c, l)| !visited[l * columns + c])
+                .collect();
+            if unvisited.is_empty() {
+                // Dead end: sometimes open a wall to another visited cell, to make a loop.
+                if !neighbors.is_empty() && self.rng.gen_bool(MAZE_LOOP_PROBABILITY) {
+                    let (c, l) = neighbors[self.rng.gen_range(0, neighbors.len())];
+                    dig(column.min(c), line.min(l), (column.max(c), line.max(l)));
+                }
+                let _ = stack.pop();
                 continue;
             }
+            let (c, l) = unvisited[self.rng.gen_range(0, unvisited.len())];
+            visited[l * columns + c] = true;
+            dig(column.min(c), line.min(l), (column.max(c), line.max(l)));
+            stack.push((c, l));
+        }
+
+        let mut uf = self.open_all(&closed);
+        self.spawn_generals_far_apart(&mut uf, true)?;
+        self.connect_generals(&mut uf)?;
+        Ok(())
+    }
+
+    /// Dig the given number of horizontal lanes of the given width between two bases, on the
+    /// left and right sides of the grid. The lanes are separated by mountain walls, and meet in a
+    /// plaza in the middle of the grid, where clusters of cities stand between the lanes. The
+    /// generals are spawned in the bases, alternately on each side. On toroidal grids, the first
+    /// and last columns are mountain walls between the bases, which would be next to each other
+    /// otherwise.
+    fn dig_lanes(&mut self, lanes: usize, width: usize) -> Result<(), GeneratorError> {
+        let (grid_width, grid_height) = (self.grid.width(), self.grid.height());
+        // Make the lanes fit in the grid, with walls between them.
+        let lanes = lanes.clamp(1, grid_height.div_ceil(2));
+        let spacing = grid_height / lanes;
+        let width = width.clamp(1, (spacing - 1).max(1));
+        let lane_lines: Vec<(usize, usize)> = (0..lanes)
+            .map(|lane| {
+                let start = lane * spacing + (spacing - width) / 2;
+                (start, start + width)
+            })
+            .collect();
+        let center = grid_width / 2;
+        let (first_line, last_line) = (lane_lines[0].0, lane_lines[lanes - 1].1);
+
+        let mut closed = vec![true; self.grid.len()];
+        for (index, is_closed) in closed.iter_mut().enumerate() {
+            let (column, line) = (index % grid_width, index / grid_width);
+            let in_base = column < LANE_BASE_WIDTH || column >= grid_width - LANE_BASE_WIDTH;
+            let in_seam =
+                self.topology == Topology::Torus && (column == 0 || column == grid_width - 1);
+            let in_lane = lane_lines.iter().any(|(s, e)| line >= *s && line < *e);
+            let in_plaza = column + 1 >= center
+                && column <= center + 1
+                && line >= first_line
+                && line < last_line;
+            *is_closed = in_seam || !(in_base || in_lane || in_plaza);
+        }
+        let mut uf = self.open_all(&closed);
+
+        // Spawn the generals in the bases. On symmetric grids, the first general's images are
+        // spawned with it.
+        let slots = self.nb_generals.div_ceil(2);
+        for i in 0..self.nb_generals {
+            if self.generals.len() >= self.nb_generals {
+                break;
+            }
+            let line = (2 * (i / 2) + 1) * grid_height / (2 * slots);
+            let column = if i % 2 == 0 { 1 } else { grid_width - 2 };
+            let index = line * grid_width + column;
+            if self.grid.is_void(index) {
+                break;
+            }
+            let spawns = if self.symmetry == Symmetry::None {
+                Some(vec![index])
+            } else {
+                self.spawns(index, self.orbit(index))
+            };
+            match spawns {
+                Some(spawns) => {
+                    let _ = self.open(index, &mut uf);
+                    self.spawn_generals(spawns);
+                }
+                None => break,
+            }
+        }
+        self.spawn_generals_far_apart(&mut uf, true)?;
+        self.connect_generals(&mut uf)?;
+
+        // Put clusters of cities in the plaza, between the lanes, or around the lane if there is
+        // only one.
+        let mut city_lines: Vec<usize> = lane_lines
+            .windows(2)
+            .map(|pair| (pair[0].1 + pair[1].0) / 2)
+            .collect();
+        if lanes == 1 {
+            city_lines.extend(first_line.checked_sub(1));
+            city_lines.extend(Some(last_line).filter(|line| *line < grid_height));
+        }
+        for line in city_lines {
+            for column in center.saturating_sub(1)..(center + 2).min(grid_width) {
+                for index in self.orbit(line * grid_width + column) {
+                    let mut tile = self.grid.get(index).borrow_mut();
+                    if tile.is_general() || self.grid.is_void(index) {
+                        continue;
+                    }
+                    tile.make_city();
+                    tile.set_units(DEFAULT_CITY_UNITS);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Open all the tiles that are not closed, with their images. Return the union-find structure
+    /// of the open tiles.
+    fn open_all(&self, closed: &[bool]) -> UnionFindRange {
+        let mut uf = UnionFindRange::with_keys_in_range(..self.grid.len());
+        for index in (0..self.grid.len()).filter(|index| !closed[*index]) {
+            let _ = self.open(index, &mut uf);
+        }
+        uf
+    }
+
+    /// Spawn the generals that are not spawned yet, one after the other, each on the tile that is
+    /// the farthest from the generals already spawned (or on a random tile for the first one). If
+    /// `prefer_open` is set, open tiles are preferred. Return an error if there is no room left
+    /// for a general.
+    fn spawn_generals_far_apart(
+        &mut self,
+        uf: &mut UnionFindRange,
+        prefer_open: bool,
+    ) -> Result<(), GeneratorError> {
+        while self.generals.len() < self.nb_generals {
+            let mut candidates: Vec<usize> = (0..self.grid.len())
+                .filter(|index| !self.grid.is_void(*index))
+                .filter(|index| self.spawns(*index, self.orbit(*index)).is_some())
+                .collect();
+            if prefer_open && candidates.iter().any(|index| self.is_open(*index)) {
+                candidates.retain(|index| self.is_open(*index));
+            }
+            // Keep the candidates that are the farthest from the other generals.
+            let distance = |index: &usize| {
+                self.generals
+                    .iter()
+                    .map(|general| self.grid.manhattan_distance(*index, *general))
+                    .min()
+                    .unwrap_or(0)
+            };
+            let farthest = candidates.iter().map(distance).max().ok_or_else(|| {
+                GeneratorError::NotEnoughRoom {
+                    nb_generals: self.nb_generals,
+                    width: self.grid.width(),
+                    height: self.grid.height(),
+                }
+            })?;
+            candidates.retain(|index| distance(index) == farthest);
+
+            let index = *candidates.choose(&mut self.rng).unwrap();
+            let spawns = self.spawns(index, self.orbit(index)).unwrap();
+            let _ = self.open(index, uf);
+            self.spawn_generals(spawns);
+        }
+        Ok(())
+    }
 
-            // Check that all the generals are connected. If so, we're done.
-            //
-            // To check whether all the generals are connected, we check that they are all
-            // connected to the first one.
-            let first_general = self.generals[0];
-            for general in self.generals.iter().skip(1) {
-                if !uf.in_same_set(first_general, *general) {
-                    continue 'outer;
+    /// Connect the generals to the first one, by digging the shortest tunnels. Return an error if
+    /// some generals cannot be connected, because void cells separate them from the first one.
+    pub(crate) fn connect_generals(
+        &mut self,
+        uf: &mut UnionFindRange,
+    ) -> Result<(), GeneratorError> {
+        for i in 1..self.generals.len() {
+            if uf.in_same_set(self.generals[0], self.generals[i]) {
+                continue;
+            }
+            for index in self.tunnel(self.generals[i], self.generals[0]) {
+                if !self.is_open(index) {
+                    let _ = self.open(index, uf);
                 }
             }
+            if !uf.in_same_set(self.generals[0], self.generals[i]) {
+                return Err(GeneratorError::Disconnected);
+            }
+        }
+        Ok(())
+    }
 
-            debug!("successfully generated a grid");
-            // For debugging print the generated grid
-            for (idx, tile) in self.grid.tiles().iter().enumerate() {
-                debug!("index {}: {:?}", idx, tile);
+    /// Return the path between two tiles that goes through the least closed tiles. If there is
+    /// no path, only the destination is returned.
+    fn tunnel(&self, from: usize, to: usize) -> Vec<usize> {
+        // Breadth first search, where going through an open tile is free.
+        let mut costs = vec![usize::MAX; self.grid.len()];
+        let mut previous = vec![None; self.grid.len()];
+        let mut queue = VecDeque::new();
+        costs[from] = 0;
+        queue.push_back(from);
+        while let Some(index) = queue.pop_front() {
+            if index == to {
+                break;
             }
-            return (self.generals, self.grid);
+            for neighbor in self.grid.direct_neighbors(index) {
+                let is_open = self.is_open(neighbor);
+                let cost = costs[index] + if is_open { 0 } else { 1 };
+                if cost < costs[neighbor] {
+                    costs[neighbor] = cost;
+                    previous[neighbor] = Some(index);
+                    if is_open {
+                        queue.push_front(neighbor);
+                    } else {
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+        let mut path = vec![to];
+        let mut current = to;
+        while let Some(index) = previous[current] {
+            path.push(index);
+            current = index;
+        }
+        path
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GeneratorError {
+    /// The generals cannot all be spawned far enough from each other on a grid of this size.
+    NotEnoughRoom {
+        nb_generals: usize,
+        width: usize,
+        height: usize,
+    },
+    /// Some generals cannot be connected to the others, because void cells separate them.
+    Disconnected,
+}
+
+impl Error for GeneratorError {
+    fn description(&self) -> &str {
+        match *self {
+            GeneratorError::NotEnoughRoom { .. } => "not enough room for the generals",
+            GeneratorError::Disconnected => "the generals cannot all be connected",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        None
+    }
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GeneratorError::NotEnoughRoom {
+                nb_generals,
+                width,
+                height,
+            } => write!(
+                f,
+                "Generator error: {} generals do not fit on a {}x{} grid",
+                nb_generals, width, height
+            ),
+            GeneratorError::Disconnected => write!(f, "Generator error: {}", self.description()),
         }
     }
 }
@@ -0,0 +1,351 @@
+use std::cell::RefCell;
+
+use fera_unionfind::UnionFindRange;
+
+use super::common::Tile;
+use super::grid::{Grid, Topology};
+use super::map_analysis::{MapAnalysis, DEFAULT_RADIUS};
+use super::map_generator::{
+    GeneratorError, GridBuilder, Shape, Strategy, Symmetry, DEFAULT_CAVE_DENSITY,
+    DEFAULT_LANE_WIDTH,
+};
+
+/// Return the image of a tile by the symmetry.
+fn image(grid: &Grid<RefCell<Tile>>, symmetry: Symmetry, index: usize) -> usize {
+    let (width, height) = (grid.width(), grid.height());
+    let (column, line) = (index % width, index / width);
+    match symmetry {
+        Symmetry::None => index,
+        Symmetry::Point => (height - 1 - line) * width + width - 1 - column,
+        Symmetry::Mirror => line * width + width - 1 - column,
+        Symmetry::Rotational => column * width + width - 1 - line,
+    }
+}
+
+fn check_symmetry(strategy: Strategy, symmetry: Symmetry, nb_generals: usize) {
+    for seed in 0..10 {
+        let (generals, grid) = GridBuilder::with_seed(nb_generals, seed)
+            .strategy(strategy)
+            .symmetry(symmetry)
+            .build()
+            .unwrap();
+        assert_eq!(generals.len(), nb_generals);
+        for index in 0..grid.len() {
+            let tile = grid.get(index).borrow();
+            let other = grid.get(image(&grid, symmetry, index)).borrow();
+            assert_eq!(tile.is_mountain(), other.is_mountain(), "seed {}", seed);
+            assert_eq!(tile.is_general(), other.is_general(), "seed {}", seed);
+        }
+        // The generals are the images of each other, in order.
+        for (i, general) in generals.iter().enumerate() {
+            let next = generals[(i + 1) % generals.len()];
+            assert_eq!(image(&grid, symmetry, *general), next);
+        }
+    }
+}
+
+#[test]
+fn test_point_symmetry() {
+    check_symmetry(Strategy::default(), Symmetry::Point, 2);
+}
+
+#[test]
+fn test_mirror_symmetry() {
+    check_symmetry(Strategy::default(), Symmetry::Mirror, 2);
+}
+
+#[test]
+fn test_rotational_symmetry() {
+    check_symmetry(Strategy::default(), Symmetry::Rotational, 4);
+}
+
+#[test]
+fn test_unsupported_symmetry() {
+    let (generals, _) = GridBuilder::with_seed(3, 0)
+        .symmetry(Symmetry::Point)
+        .build()
+        .unwrap();
+    assert_eq!(generals.len(), 3);
+}
+
+#[test]
+fn test_caves() {
+    let strategy = Strategy::Cave {
+        density: DEFAULT_CAVE_DENSITY,
+    };
+    for seed in 0..10 {
+        let (generals, grid) = GridBuilder::with_seed(3, seed)
+            .strategy(strategy)
+            .build()
+            .unwrap();
+        assert_eq!(generals.len(), 3);
+        let analysis = MapAnalysis::new(&grid, &generals, DEFAULT_RADIUS);
+        for general in &analysis.generals {
+            assert!(
+                general.distances.iter().all(Option::is_some),
+                "seed {}",
+                seed
+            );
+        }
+    }
+    check_symmetry(strategy, Symmetry::Point, 2);
+    check_symmetry(strategy, Symmetry::Rotational, 4);
+}
+
+#[test]
+fn test_dense_caves() {
+    // Even when the caves are too small to spawn the generals, a valid grid is generated.
+    let strategy = Strategy::Cave { density: 0.95 };
+    let (generals, grid) = GridBuilder::with_seed(2, 0)
+        .strategy(strategy)
+        .build()
+        .unwrap();
+    let analysis = MapAnalysis::new(&grid, &generals, DEFAULT_RADIUS);
+    assert!(analysis.generals[0].nearest_general().is_some());
+}
+
+/// Check that the generals of grids generated with the given strategy are connected.
+fn check_connected(strategy: Strategy, nb_generals: usize) {
+    for seed in 0..10 {
+        let (generals, grid) = GridBuilder::with_seed(nb_generals, seed)
+            .strategy(strategy)
+            .build()
+            .unwrap();
+        assert_eq!(generals.len(), nb_generals);
+        let analysis = MapAnalysis::new(&grid, &generals, DEFAULT_RADIUS);
+        for general in &analysis.generals {
+            assert!(
+                general.distances.iter().all(Option::is_some),
+                "seed {}",
+                seed
+            );
+        }
+    }
+}
+
+#[test]
+fn test_random() {
+    // Even with many generals, a grid is generated quickly and the generals are connected.
+    check_connected(Strategy::default(), 2);
+    check_connected(Strategy::default(), 8);
+    check_connected(Strategy::Random { density: 0.1 }, 16);
+}
+
+#[test]
+fn test_maze() {
+    check_connected(Strategy::Maze { width: 1 }, 2);
+    check_connected(Strategy::Maze { width: 2 }, 5);
+    check_symmetry(Strategy::Maze { width: 1 }, Symmetry::Mirror, 2);
+    // Corridors wider than the grid are narrowed.
+    check_connected(Strategy::Maze { width: 15 }, 2);
+    check_connected(Strategy::Maze { width: 30 }, 2);
+}
+
+#[test]
+fn test_lanes() {
+    check_connected(Strategy::Lanes { lanes: 3, width: 2 }, 2);
+    check_connected(Strategy::Lanes { lanes: 1, width: 1 }, 4);
+    check_connected(
+        Strategy::Lanes {
+            lanes: 50,
+            width: 50,
+        },
+        3,
+    );
+    check_symmetry(Strategy::Lanes { lanes: 3, width: 2 }, Symmetry::Point, 2);
+
+    // The generals are in the bases, on opposite sides, and there are cities in the middle.
+    let (generals, grid) = GridBuilder::with_seed(2, 0)
+        .strategy(Strategy::Lanes { lanes: 2, width: 2 })
+        .build()
+        .unwrap();
+    assert_eq!(generals[0] % grid.width(), 1);
+    assert_eq!(generals[1] % grid.width(), grid.width() - 2);
+    let center = grid.width() / 2;
+    assert!(
+        (0..grid.height()).any(|line| grid.get(line * grid.width() + center).borrow().is_city())
+    );
+}
+
+/// Check that the grids generated with the given shape and strategy have void cells, and that
+/// the generals are on connected tiles that are not void.
+fn check_shape(shape: Shape, strategy: Strategy, nb_generals: usize) {
+    for seed in 0..5 {
+        let (generals, grid) = GridBuilder::with_seed(nb_generals, seed)
+            .strategy(strategy)
+            .shape(shape)
+            .build()
+            .unwrap();
+        assert!(grid.nb_playable() < grid.len());
+        for index in (0..grid.len()).filter(|index| grid.is_void(*index)) {
+            assert!(grid.get(index).borrow().is_mountain());
+        }
+        assert!(generals.iter().all(|general| !grid.is_void(*general)));
+        let analysis = MapAnalysis::new(&grid, &generals, DEFAULT_RADIUS);
+        for general in &analysis.generals {
+            assert!(
+                general.distances.iter().all(Option::is_some),
+                "{:?} {:?}, seed {}",
+                shape,
+                strategy,
+                seed
+            );
+        }
+    }
+}
+
+#[test]
+fn test_shapes() {
+    for shape in &[Shape::Island, Shape::Ring, Shape::Cross] {
+        check_shape(*shape, Strategy::default(), 2);
+        check_shape(*shape, Strategy::default(), 6);
+        check_shape(
+            *shape,
+            Strategy::Cave {
+                density: DEFAULT_CAVE_DENSITY,
+            },
+            3,
+        );
+        check_shape(*shape, Strategy::Maze { width: 1 }, 2);
+        check_shape(*shape, Strategy::Lanes { lanes: 3, width: 2 }, 4);
+    }
+
+    // The shapes are symmetric.
+    let (_, grid) = GridBuilder::with_seed(4, 0)
+        .shape(Shape::Ring)
+        .symmetry(Symmetry::Rotational)
+        .build()
+        .unwrap();
+    for index in 0..grid.len() {
+        for symmetry in &[Symmetry::Point, Symmetry::Mirror, Symmetry::Rotational] {
+            let image = image(&grid, *symmetry, index);
+            assert_eq!(grid.is_void(index), grid.is_void(image));
+        }
+    }
+    // The middle of a ring is void, but not the middle of a cross.
+    let middle = |grid: &Grid<RefCell<Tile>>| grid.height() / 2 * grid.width() + grid.width() / 2;
+    assert!(grid.is_void(middle(&grid)));
+    let (_, grid) = GridBuilder::with_seed(2, 0)
+        .shape(Shape::Cross)
+        .build()
+        .unwrap();
+    assert!(grid.is_void(0) && !grid.is_void(middle(&grid)));
+}
+
+#[test]
+fn test_torus() {
+    for strategy in &[
+        Strategy::default(),
+        Strategy::Cave {
+            density: DEFAULT_CAVE_DENSITY,
+        },
+        Strategy::Maze { width: 1 },
+    ] {
+        for seed in 0..5 {
+            let (generals, grid) = GridBuilder::with_seed(4, seed)
+                .strategy(*strategy)
+                .topology(Topology::Torus)
+                .build()
+                .unwrap();
+            assert_eq!(grid.topology(), Topology::Torus);
+            let analysis = MapAnalysis::new(&grid, &generals, DEFAULT_RADIUS);
+            for general in &analysis.generals {
+                assert!(general.distances.iter().all(Option::is_some));
+            }
+        }
+    }
+
+    // The bases of lane grids are separated by mountain walls along the edges.
+    let (_, grid) = GridBuilder::with_seed(2, 0)
+        .strategy(Strategy::Lanes { lanes: 3, width: 2 })
+        .topology(Topology::Torus)
+        .symmetry(Symmetry::Mirror)
+        .build()
+        .unwrap();
+    for line in 0..grid.height() {
+        let first = line * grid.width();
+        assert!(grid.get(first).borrow().is_mountain());
+        assert!(grid.get(first + grid.width() - 1).borrow().is_mountain());
+    }
+}
+
+#[test]
+fn test_strategy_spec() {
+    assert_eq!(Strategy::from_spec("random"), Some(Strategy::default()));
+    assert_eq!(
+        Strategy::from_spec("cave"),
+        Some(Strategy::Cave {
+            density: DEFAULT_CAVE_DENSITY
+        })
+    );
+    assert_eq!(
+        Strategy::from_spec("cave:0.3"),
+        Some(Strategy::Cave { density: 0.3 })
+    );
+    assert_eq!(Strategy::from_spec("cave:1.5"), None);
+    assert_eq!(
+        Strategy::from_spec("random:0.3"),
+        Some(Strategy::Random { density: 0.3 })
+    );
+    assert_eq!(Strategy::from_spec("random:0"), None);
+    assert_eq!(
+        Strategy::from_spec("maze"),
+        Some(Strategy::Maze { width: 1 })
+    );
+    assert_eq!(
+        Strategy::from_spec("maze:2"),
+        Some(Strategy::Maze { width: 2 })
+    );
+    assert_eq!(Strategy::from_spec("maze:0"), None);
+    assert_eq!(
+        Strategy::from_spec("lanes:4"),
+        Some(Strategy::Lanes {
+            lanes: 4,
+            width: DEFAULT_LANE_WIDTH
+        })
+    );
+    assert_eq!(
+        Strategy::from_spec("lanes:2:3"),
+        Some(Strategy::Lanes { lanes: 2, width: 3 })
+    );
+    assert_eq!(Strategy::from_spec("lanes:2:3:1"), None);
+}
+
+#[test]
+fn test_shape_name() {
+    assert_eq!(Shape::from_name("rectangle"), Some(Shape::Rectangle));
+    assert_eq!(Shape::from_name("ring"), Some(Shape::Ring));
+    assert_eq!(Shape::from_name("circle"), None);
+}
+
+#[test]
+fn test_connect_generals() {
+    // Two generals on a 5x3 grid of mountains
+    let builder = |void_column: Option<usize>| {
+        let mut builder = GridBuilder::with_seed(2, 0);
+        builder.grid = Grid::new(|_| RefCell::new(Tile::new()), 5, 3);
+        for line in 0..3 {
+            if let Some(column) = void_column {
+                builder.grid.make_void(line * 5 + column);
+            }
+        }
+        for general in &[0, 14] {
+            builder.grid.get(*general).borrow_mut().make_general();
+        }
+        builder.generals = vec![0, 14];
+        builder
+    };
+
+    let mut connected = builder(None);
+    let mut uf = UnionFindRange::with_keys_in_range(..15);
+    assert_eq!(connected.connect_generals(&mut uf), Ok(()));
+    assert!(uf.in_same_set(0, 14));
+
+    // A column of void cells separates the generals.
+    let mut split = builder(Some(2));
+    let mut uf = UnionFindRange::with_keys_in_range(..15);
+    assert_eq!(
+        split.connect_generals(&mut uf),
+        Err(GeneratorError::Disconnected)
+    );
+}
@@ -0,0 +1,173 @@
+//! This module contains the pools of maps public games are played on.
+//!
+//! A pool is loaded from a directory of map files (see `MapFile`). Each time a game is created, a
+//! map of the pool that is made for the game's number of players is picked, according to the
+//! pool's rotation policy. When no map of the pool is compatible, the game is played on a random
+//! map instead.
+//!
+//! For the weighted rotation, the directory can contain a `weights.json` file, that gives the
+//! weight of the maps by file name, for instance `{"crossroads.txt": 3}`. Maps that are not listed
+//! have a weight of 1.
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::Path;
+
+use rand::{rngs::StdRng, FromEntropy, Rng, SeedableRng};
+use serde_json;
+
+use super::map_analysis::{MapAnalysis, DEFAULT_RADIUS};
+use super::map_file::{MapError, MapFile};
+
+/// Name of the file that gives the weight of the maps of a directory
+pub const WEIGHTS_FILE: &str = "weights.json";
+
+/// How the maps of a pool are picked.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rotation {
+    /// Pick a compatible map at random.
+    Random,
+    /// Pick the maps in turn, in the order of their file names, skipping the ones that are not
+    /// compatible.
+    RoundRobin,
+    /// Pick a compatible map at random, according to the maps' weights.
+    Weighted,
+}
+
+impl Rotation {
+    /// Return the rotation with the given name: `random`, `round-robin` or `weighted`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "random" => Some(Rotation::Random),
+            "round-robin" => Some(Rotation::RoundRobin),
+            "weighted" => Some(Rotation::Weighted),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MapPool {
+    /// The maps, with their weight
+    maps: Vec<(MapFile, u32)>,
+    rotation: Rotation,
+    /// Index of the next map to try, for the round-robin rotation
+    next: usize,
+    rng: StdRng,
+}
+
+impl MapPool {
+    /// Return a pool of the given maps, that all have the same weight.
+    pub fn new(maps: Vec<MapFile>, rotation: Rotation) -> Self {
+        MapPool {
+            maps: maps.into_iter().map(|map| (map, 1)).collect(),
+            rotation,
+            next: 0,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Return a pool of the given maps, with their weight, that always picks the same maps for a
+    /// given seed.
+    pub fn with_seed(maps: Vec<(MapFile, u32)>, rotation: Rotation, seed: u64) -> Self {
+        MapPool {
+            maps,
+            rotation,
+            next: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Load all the maps of the given directory. Files that are not valid maps are skipped.
+    pub fn load<P: AsRef<Path>>(directory: P, rotation: Rotation) -> Result<Self, MapError> {
+        let directory = directory.as_ref();
+        let weights: HashMap<String, u32> = match File::open(directory.join(WEIGHTS_FILE)) {
+            Ok(file) => serde_json::from_reader(file)?,
+            Err(_) => HashMap::new(),
+        };
+
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_file() && path.file_name().is_some_and(|name| name != WEIGHTS_FILE) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut pool = MapPool::new(Vec::new(), rotation);
+        for path in paths {
+            match MapFile::load(&path).and_then(|map| map.build().map(|built| (map, built))) {
+                Ok((map, (generals, grid))) => {
+                    let file_name = path.file_name().unwrap().to_string_lossy();
+                    let weight = weights.get(file_name.as_ref()).cloned().unwrap_or(1);
+                    info!("adding {} to the map pool", path.display());
+                    debug!(
+                        "analysis of {}: {:?}",
+                        path.display(),
+                        MapAnalysis::new(&grid, &generals, DEFAULT_RADIUS)
+                    );
+                    pool.maps.push((map, weight));
+                }
+                Err(e) => warn!("skipping {}: {}", path.display(), e),
+            }
+        }
+        Ok(pool)
+    }
+
+    /// Return the number of maps in the pool.
+    pub fn len(&self) -> usize {
+        self.maps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.is_empty()
+    }
+
+    /// Pick a map for a game with the given number of players. Return `None` if no map of the
+    /// pool is made for that number of players.
+    pub fn pick(&mut self, nb_players: usize) -> Option<&MapFile> {
+        let compatible: Vec<usize> = (0..self.maps.len())
+            .filter(|i| self.maps[*i].0.nb_players() == nb_players)
+            .collect();
+        if compatible.is_empty() {
+            return None;
+        }
+        let index = match self.rotation {
+            Rotation::Random => compatible[self.rng.gen_range(0, compatible.len())],
+            Rotation::RoundRobin => {
+                let index = (0..self.maps.len())
+                    .map(|i| (self.next + i) % self.maps.len())
+                    .find(|i| compatible.contains(i))
+                    .unwrap();
+                self.next = index + 1;
+                index
+            }
+            Rotation::Weighted => {
+                // Maps with a weight of 0 are never picked. The weights are summed as `u64`, so
+                // that big weights do not overflow.
+                let weighted: Vec<(usize, u64)> = compatible
+                    .iter()
+                    .map(|i| (*i, u64::from(self.maps[*i].1)))
+                    .filter(|(_, weight)| *weight > 0)
+                    .collect();
+                let total: u64 = weighted.iter().map(|(_, weight)| weight).sum();
+                if total == 0 {
+                    return None;
+                }
+                let mut draw = self.rng.gen_range(0, total);
+                weighted
+                    .iter()
+                    .find(|(_, weight)| {
+                        if draw < *weight {
+                            return true;
+                        }
+                        draw -= weight;
+                        false
+                    })
+                    .unwrap()
+                    .0
+            }
+        };
+        Some(&self.maps[index].0)
+    }
+}
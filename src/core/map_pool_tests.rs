@@ -0,0 +1,81 @@
+use super::map_file::MapFile;
+use super::map_pool::{MapPool, Rotation};
+
+fn map(name: &str, text: &str) -> MapFile {
+    let mut map = MapFile::from_text(text).unwrap();
+    map.name = Some(name.to_string());
+    map
+}
+
+fn get_maps() -> Vec<(MapFile, u32)> {
+    vec![
+        (map("small", "S . S"), 1),
+        (map("three", "S S S"), 1),
+        (map("large", "S . . . S"), 0),
+        (map("wide", "S . . S"), 3),
+    ]
+}
+
+fn pick(pool: &mut MapPool, nb_players: usize) -> Option<String> {
+    pool.pick(nb_players).map(|map| map.name.clone().unwrap())
+}
+
+#[test]
+fn test_round_robin() {
+    let mut pool = MapPool::with_seed(get_maps(), Rotation::RoundRobin, 0);
+    assert_eq!(pick(&mut pool, 2).unwrap(), "small");
+    assert_eq!(pick(&mut pool, 2).unwrap(), "large");
+    assert_eq!(pick(&mut pool, 3).unwrap(), "three");
+    assert_eq!(pick(&mut pool, 2).unwrap(), "large");
+    assert_eq!(pick(&mut pool, 2).unwrap(), "wide");
+    assert_eq!(pick(&mut pool, 2).unwrap(), "small");
+    assert_eq!(pick(&mut pool, 4), None);
+}
+
+#[test]
+fn test_random() {
+    let mut pool = MapPool::with_seed(get_maps(), Rotation::Random, 0);
+    for _ in 0..20 {
+        assert_eq!(pick(&mut pool, 3).unwrap(), "three");
+        assert_ne!(pick(&mut pool, 2).unwrap(), "three");
+    }
+    assert_eq!(pick(&mut pool, 4), None);
+    assert_eq!(MapPool::new(Vec::new(), Rotation::Random).pick(2), None);
+}
+
+#[test]
+fn test_weighted() {
+    let mut pool = MapPool::with_seed(get_maps(), Rotation::Weighted, 0);
+    let mut wide = 0;
+    for _ in 0..100 {
+        let name = pick(&mut pool, 2).unwrap();
+        assert_ne!(name, "large");
+        if name == "wide" {
+            wide += 1;
+        }
+    }
+    assert!(wide > 50);
+}
+
+#[test]
+fn test_weighted_big_weights() {
+    let maps = vec![
+        (map("small", "S . S"), u32::MAX),
+        (map("large", "S . . . S"), 0),
+        (map("wide", "S . . S"), u32::MAX),
+    ];
+    let mut pool = MapPool::with_seed(maps, Rotation::Weighted, 0);
+    let mut wide = 0;
+    for _ in 0..100 {
+        let name = pick(&mut pool, 2).unwrap();
+        assert_ne!(name, "large");
+        if name == "wide" {
+            wide += 1;
+        }
+    }
+    assert!(wide > 20 && wide < 80);
+
+    let maps = vec![(map("small", "S . S"), 0), (map("wide", "S . . S"), 0)];
+    let mut pool = MapPool::with_seed(maps, Rotation::Weighted, 0);
+    assert_eq!(pick(&mut pool, 2), None);
+}
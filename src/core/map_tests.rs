@@ -1,6 +1,7 @@
 use std::cell::RefCell;
 
-use super::common::{Direction, Move, Tile};
+use super::common::{Direction, Move, MoveOutcome, Teams, Tile};
+use super::game::CaptureRule;
 use super::grid::Grid;
 use super::map::Map;
 
@@ -82,11 +83,15 @@ fn get_map() -> Map {
 #[test]
 fn test_transfer_units() {
     let mut map = get_map();
-    map.perform_move(Move {
-        player: PLAYER_2,
-        from: GENERAL,
-        direction: Direction::Down,
-    })
+    map.perform_move(
+        Move {
+            player: PLAYER_2,
+            from: GENERAL,
+            direction: Direction::Down,
+        },
+        &Teams::default(),
+        CaptureRule::Full,
+    )
     .unwrap();
     let src = map.get(GENERAL);
     let dst = map.get(OPEN_2);
@@ -101,11 +106,15 @@ fn test_transfer_units() {
 #[test]
 fn test_conquer_city() {
     let mut map = get_map();
-    map.perform_move(Move {
-        player: PLAYER_2,
-        from: GENERAL,
-        direction: Direction::Right,
-    })
+    map.perform_move(
+        Move {
+            player: PLAYER_2,
+            from: GENERAL,
+            direction: Direction::Right,
+        },
+        &Teams::default(),
+        CaptureRule::Full,
+    )
     .unwrap();
     let src = map.get(GENERAL);
     let dst = map.get(FORTRESS);
@@ -124,11 +133,15 @@ fn test_conquer_city() {
 #[test]
 fn test_conquer_general() {
     let mut map = get_map();
-    map.perform_move(Move {
-        player: PLAYER_1,
-        from: OPEN_1,
-        direction: Direction::Right,
-    })
+    map.perform_move(
+        Move {
+            player: PLAYER_1,
+            from: OPEN_1,
+            direction: Direction::Right,
+        },
+        &Teams::default(),
+        CaptureRule::Full,
+    )
     .unwrap();
     let src = map.get(OPEN_1);
     let dst = map.get(GENERAL);
@@ -164,7 +177,7 @@ fn test_conquer_reinforce() {
     assert_eq!(map.get(OPEN_2).units(), 4);
     assert_eq!(map.get(EMPTY_3).units(), 0);
 
-    map.reinforce(true);
+    map.reinforce(true, &[]);
     assert_eq!(map.get(EMPTY_1).units(), 0);
     assert_eq!(map.get(EMPTY_2).units(), 0);
     assert_eq!(map.get(OPEN_1).units(), 21);
@@ -173,3 +186,65 @@ fn test_conquer_reinforce() {
     assert_eq!(map.get(OPEN_2).units(), 5);
     assert_eq!(map.get(EMPTY_3).units(), 0);
 }
+
+#[test]
+fn test_legal_moves() {
+    let map = get_map();
+    let moves: Vec<(usize, Direction, MoveOutcome)> = map
+        .legal_moves(PLAYER_2, &Teams::default())
+        .into_iter()
+        .map(|(mv, outcome)| (mv.from, mv.direction, outcome))
+        .collect();
+    assert_eq!(
+        moves,
+        vec![
+            (
+                GENERAL,
+                Direction::Right,
+                MoveOutcome::TileCaptured(Some(PLAYER_1))
+            ),
+            (GENERAL, Direction::Left, MoveOutcome::StatuQuo),
+            (GENERAL, Direction::Up, MoveOutcome::TileCaptured(None)),
+            (GENERAL, Direction::Down, MoveOutcome::StatuQuo),
+            (OPEN_2, Direction::Up, MoveOutcome::StatuQuo),
+        ]
+    );
+
+    // Open1 cannot move left (out of the grid), nor up and down (mountains)
+    let moves: Vec<(usize, Direction)> = map
+        .legal_moves(PLAYER_1, &Teams::default())
+        .into_iter()
+        .map(|(mv, _)| (mv.from, mv.direction))
+        .collect();
+    assert_eq!(
+        moves,
+        vec![
+            (OPEN_1, Direction::Right),
+            (FORTRESS, Direction::Left),
+            (FORTRESS, Direction::Up),
+        ]
+    );
+}
+
+#[test]
+fn test_path() {
+    let map = get_map();
+    assert_eq!(
+        map.path(PLAYER_1, OPEN_1, EMPTY_2),
+        Some(vec![OPEN_1, GENERAL, EMPTY_1, EMPTY_2])
+    );
+    assert_eq!(map.path(PLAYER_1, OPEN_1, OPEN_1), Some(vec![OPEN_1]));
+    // Empty3 is surrounded by mountains
+    assert_eq!(map.path(PLAYER_1, OPEN_1, EMPTY_3), None);
+
+    // The city is in the fog of war for player 1, so it looks like a mountain.
+    map.get_mut(FORTRESS).hide_from(PLAYER_1);
+    assert_eq!(
+        map.path(PLAYER_1, GENERAL, EMPTY_2),
+        Some(vec![GENERAL, EMPTY_1, EMPTY_2])
+    );
+    assert_eq!(
+        map.path(PLAYER_2, GENERAL, FORTRESS),
+        Some(vec![GENERAL, FORTRESS])
+    );
+}
@@ -2,14 +2,48 @@ mod common;
 mod game;
 mod grid;
 mod map;
+mod map_analysis;
+mod map_file;
 mod map_generator;
+mod map_pool;
+mod observation;
+mod queue;
+mod sim;
 
 #[cfg(test)]
 mod common_tests;
 #[cfg(test)]
+mod game_tests;
+#[cfg(test)]
 mod grid_tests;
 #[cfg(test)]
+mod map_analysis_tests;
+#[cfg(test)]
+mod map_file_tests;
+#[cfg(test)]
+mod map_generator_tests;
+#[cfg(test)]
+mod map_pool_tests;
+#[cfg(test)]
 mod map_tests;
+#[cfg(test)]
+mod observation_tests;
+#[cfg(test)]
+mod queue_tests;
+#[cfg(test)]
+mod sim_tests;
 
-pub use self::common::{Action, Move, PlayerId, Tile};
-pub use self::game::{Game, Update};
+pub use self::common::{
+    Action, Move, MoveOutcome, Player, PlayerId, StampedAction, Teams, Tile, TileKind, TileState,
+};
+pub use self::game::{CaptureRule, Game, ResignPolicy, Update};
+pub use self::grid::Topology;
+pub use self::map_analysis::Fairness;
+pub use self::map_file::MapFile;
+pub use self::map_generator::{
+    max_grid_size, GeneratorConfig, GeneratorError, Shape, Strategy, Symmetry,
+};
+pub use self::map_pool::{MapPool, Rotation};
+pub use self::observation::{Observation, NB_PLANES};
+pub use self::queue::MoveQueue;
+pub use self::sim::State;
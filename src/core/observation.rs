@@ -0,0 +1,141 @@
+//! This module contains code to encode what a player sees of the map as a stack of fixed-size
+//! planes, which is the format most machine learning frameworks expect.
+//!
+//! Each plane is a `max_height x max_width` grid of `u16`. The map occupies the top left corner
+//! of each plane, and the rest of the planes is padding. Padding cells are encoded as mountains,
+//! so that they look like regular obstacles.
+//!
+//! What is encoded is exactly what the player is allowed to know: units are only visible on the
+//! tiles the player or its teammates can see, and tiles in the fog of war only show whether they
+//! are an obstacle (mountains and cities look the same in the fog of war, see `Update::filtered`).
+use super::common::{PlayerId, Tile};
+use super::map::Map;
+
+/// Number of units on the tiles owned by the player
+pub const OWN_UNITS: usize = 0;
+/// Number of units on the tiles owned by the players who are not allies of the player
+pub const ENEMY_UNITS: usize = 1;
+/// Number of units on the tiles that are not owned by anyone
+pub const NEUTRAL_UNITS: usize = 2;
+/// `1` if the tile is a mountain, or looks like one because it's in the fog of war
+pub const MOUNTAINS: usize = 3;
+/// `1` if the tile is a visible city
+pub const CITIES: usize = 4;
+/// `1` if the tile is a visible general
+pub const GENERALS: usize = 5;
+/// `1` if the tile is currently visible by the player or one of its teammates
+pub const VISIBLE: usize = 6;
+/// `1` if the tile is in the fog of war, but the player or one of its teammates has already seen
+/// it before
+pub const FOGGED_KNOWN: usize = 7;
+/// Number of units on the tiles owned by the player's teammates
+pub const ALLIED_UNITS: usize = 8;
+/// Total number of planes
+pub const NB_PLANES: usize = 9;
+
+/// A player's view of the map, encoded as planes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    width: usize,
+    height: usize,
+    /// The planes, stored plane by plane, then line by line.
+    data: Vec<u16>,
+}
+
+impl Observation {
+    /// Encode the given player's view of the map. Teammates share their vision, so the player
+    /// also sees what the given allies see. The planes are `max_width` wide and `max_height`
+    /// high.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map does not fit in the planes.
+    pub fn encode(
+        map: &Map,
+        player: PlayerId,
+        allies: &[PlayerId],
+        max_width: usize,
+        max_height: usize,
+    ) -> Self {
+        assert!(
+            map.width() <= max_width && map.height() <= max_height,
+            "a {}x{} map does not fit in {}x{} planes",
+            map.width(),
+            map.height(),
+            max_width,
+            max_height
+        );
+        let mut observation = Observation {
+            width: max_width,
+            height: max_height,
+            data: vec![0; NB_PLANES * max_width * max_height],
+        };
+
+        // Start by marking everything as mountains, so that the padding is already encoded.
+        for value in observation.plane_mut(MOUNTAINS) {
+            *value = 1;
+        }
+
+        let sees = |tile: &Tile| {
+            tile.is_visible_by(player) || allies.iter().any(|ally| tile.is_visible_by(*ally))
+        };
+        let discovered = |tile: &Tile| {
+            tile.is_discovered_by(player) || allies.iter().any(|ally| tile.is_discovered_by(*ally))
+        };
+        for (index, tile) in map.iter().enumerate() {
+            let cell = (index / map.width()) * max_width + index % map.width();
+            let mut set = |plane: usize, value: u16| observation.plane_mut(plane)[cell] = value;
+
+            if !sees(&tile) {
+                set(MOUNTAINS, (tile.is_mountain() || tile.is_city()) as u16);
+                set(FOGGED_KNOWN, discovered(&tile) as u16);
+                continue;
+            }
+
+            set(VISIBLE, 1);
+            set(MOUNTAINS, tile.is_mountain() as u16);
+            set(CITIES, tile.is_city() as u16);
+            set(GENERALS, tile.is_general() as u16);
+            let units_plane = match tile.owner() {
+                Some(owner) if owner == player => OWN_UNITS,
+                Some(owner) if allies.contains(&owner) => ALLIED_UNITS,
+                Some(_) => ENEMY_UNITS,
+                None => NEUTRAL_UNITS,
+            };
+            set(units_plane, tile.units());
+        }
+        observation
+    }
+
+    /// Return the shape of the observation, as `(planes, height, width)`
+    pub fn shape(&self) -> (usize, usize, usize) {
+        (NB_PLANES, self.height, self.width)
+    }
+
+    /// Return the given plane. Values are stored line by line.
+    pub fn plane(&self, plane: usize) -> &[u16] {
+        let size = self.width * self.height;
+        &self.data[plane * size..(plane + 1) * size]
+    }
+
+    fn plane_mut(&mut self, plane: usize) -> &mut [u16] {
+        let size = self.width * self.height;
+        &mut self.data[plane * size..(plane + 1) * size]
+    }
+
+    /// Return the value of the given plane at the given position.
+    pub fn get(&self, plane: usize, column: usize, line: usize) -> u16 {
+        self.plane(plane)[line * self.width + column]
+    }
+
+    /// Return the planes as little-endian `u16`, plane by plane, then line by line. With numpy,
+    /// they can be loaded with `np.frombuffer(data, dtype="<u2").reshape(shape)`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.data.len() * 2);
+        for value in &self.data {
+            bytes.push(*value as u8);
+            bytes.push((*value >> 8) as u8);
+        }
+        bytes
+    }
+}
@@ -0,0 +1,142 @@
+use std::cell::RefCell;
+
+use super::common::Tile;
+use super::grid::Grid;
+use super::map::Map;
+use super::observation::*;
+
+const PLAYER_1: usize = 1;
+const PLAYER_2: usize = 2;
+
+/// Return a map with two players, that looks like:
+///
+/// ```
+/// General[1]  Open[3]   Open
+/// Mountain    City[40]  General[2]
+/// ```
+///
+/// Player 1 sees the left and middle columns. Player 2 sees the middle and right columns, and has
+/// already seen the top left tile.
+fn get_map() -> Map {
+    let grid = Grid::new(|_| RefCell::new(Tile::new()), 3, 2);
+    let set = |index: usize, kind: fn(&mut Tile), owner: Option<usize>, units: u16| {
+        let mut tile = grid.get(index).borrow_mut();
+        kind(&mut tile);
+        tile.set_owner(owner);
+        tile.set_units(units);
+    };
+    set(0, Tile::make_general, Some(PLAYER_1), 5);
+    set(1, Tile::make_open, Some(PLAYER_1), 3);
+    set(2, Tile::make_open, None, 0);
+    set(4, Tile::make_city, None, 40);
+    set(5, Tile::make_general, Some(PLAYER_2), 7);
+
+    for index in &[0, 1, 3, 4] {
+        grid.get(*index).borrow_mut().reveal_to(PLAYER_1);
+    }
+    for index in &[0, 1, 2, 4, 5] {
+        grid.get(*index).borrow_mut().reveal_to(PLAYER_2);
+    }
+    grid.get(0).borrow_mut().hide_from(PLAYER_2);
+    Map::from_grid(grid)
+}
+
+#[test]
+fn test_shape_and_padding() {
+    let observation = Observation::encode(&get_map(), PLAYER_1, &[], 4, 3);
+    assert_eq!(observation.shape(), (NB_PLANES, 3, 4));
+    assert_eq!(observation.to_bytes().len(), NB_PLANES * 3 * 4 * 2);
+
+    // Padding looks like mountains, and is never visible
+    for &(column, line) in &[(3, 0), (3, 1), (0, 2), (3, 2)] {
+        assert_eq!(observation.get(MOUNTAINS, column, line), 1);
+        assert_eq!(observation.get(VISIBLE, column, line), 0);
+        assert_eq!(observation.get(FOGGED_KNOWN, column, line), 0);
+    }
+}
+
+#[test]
+fn test_visible_tiles() {
+    let observation = Observation::encode(&get_map(), PLAYER_1, &[], 4, 3);
+    assert_eq!(observation.get(OWN_UNITS, 0, 0), 5);
+    assert_eq!(observation.get(GENERALS, 0, 0), 1);
+    assert_eq!(observation.get(OWN_UNITS, 1, 0), 3);
+    assert_eq!(observation.get(MOUNTAINS, 0, 1), 1);
+    assert_eq!(observation.get(CITIES, 1, 1), 1);
+    assert_eq!(observation.get(NEUTRAL_UNITS, 1, 1), 40);
+    for &(column, line) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+        assert_eq!(observation.get(VISIBLE, column, line), 1);
+    }
+
+    let observation = Observation::encode(&get_map(), PLAYER_2, &[], 3, 2);
+    assert_eq!(observation.get(OWN_UNITS, 2, 1), 7);
+    assert_eq!(observation.get(ENEMY_UNITS, 1, 0), 3);
+    assert_eq!(observation.get(ENEMY_UNITS, 0, 0), 0);
+}
+
+#[test]
+fn test_fog_of_war() {
+    let observation = Observation::encode(&get_map(), PLAYER_1, &[], 3, 2);
+    // The enemy general is not visible: no unit, no general, it just looks like an open tile
+    assert_eq!(observation.get(VISIBLE, 2, 1), 0);
+    assert_eq!(observation.get(ENEMY_UNITS, 2, 1), 0);
+    assert_eq!(observation.get(GENERALS, 2, 1), 0);
+    assert_eq!(observation.get(MOUNTAINS, 2, 1), 0);
+    assert_eq!(observation.get(FOGGED_KNOWN, 2, 1), 0);
+
+    // The city is visible by player 2, but player 1 only sees an obstacle
+    let observation = Observation::encode(&get_map(), PLAYER_2, &[], 3, 2);
+    assert_eq!(observation.get(CITIES, 1, 1), 1);
+    let observation = Observation::encode(&get_map(), PLAYER_1, &[], 3, 2);
+    assert_eq!(observation.get(CITIES, 1, 1), 1);
+    let map = get_map();
+    map.get_mut(4).hide_from(PLAYER_1);
+    let observation = Observation::encode(&map, PLAYER_1, &[], 3, 2);
+    assert_eq!(observation.get(CITIES, 1, 1), 0);
+    assert_eq!(observation.get(MOUNTAINS, 1, 1), 1);
+    assert_eq!(observation.get(NEUTRAL_UNITS, 1, 1), 0);
+    assert_eq!(observation.get(FOGGED_KNOWN, 1, 1), 1);
+
+    // Player 2 saw the top left tile before
+    let observation = Observation::encode(&get_map(), PLAYER_2, &[], 3, 2);
+    assert_eq!(observation.get(VISIBLE, 0, 0), 0);
+    assert_eq!(observation.get(FOGGED_KNOWN, 0, 0), 1);
+    assert_eq!(observation.get(ENEMY_UNITS, 0, 0), 0);
+    assert_eq!(observation.get(GENERALS, 0, 0), 0);
+}
+
+#[test]
+fn test_team_vision() {
+    // Teammates share their vision, and their units are not encoded as enemies.
+    let observation = Observation::encode(&get_map(), PLAYER_1, &[PLAYER_2], 3, 2);
+    assert_eq!(observation.get(VISIBLE, 2, 1), 1);
+    assert_eq!(observation.get(GENERALS, 2, 1), 1);
+    assert_eq!(observation.get(ALLIED_UNITS, 2, 1), 7);
+    assert_eq!(observation.get(ENEMY_UNITS, 2, 1), 0);
+    assert_eq!(observation.get(OWN_UNITS, 0, 0), 5);
+    assert_eq!(observation.get(ALLIED_UNITS, 0, 0), 0);
+    for value in observation.plane(VISIBLE) {
+        assert_eq!(*value, 1);
+    }
+
+    let observation = Observation::encode(&get_map(), PLAYER_2, &[PLAYER_1], 3, 2);
+    assert_eq!(observation.get(VISIBLE, 0, 0), 1);
+    assert_eq!(observation.get(FOGGED_KNOWN, 0, 0), 0);
+    assert_eq!(observation.get(ALLIED_UNITS, 0, 0), 5);
+    assert_eq!(observation.get(ALLIED_UNITS, 1, 0), 3);
+    assert_eq!(observation.get(ENEMY_UNITS, 1, 0), 0);
+
+    // A tile that a teammate saw before is known.
+    let map = get_map();
+    map.get_mut(0).hide_from(PLAYER_1);
+    let observation = Observation::encode(&map, PLAYER_2, &[PLAYER_1], 3, 2);
+    assert_eq!(observation.get(VISIBLE, 0, 0), 0);
+    assert_eq!(observation.get(FOGGED_KNOWN, 0, 0), 1);
+    assert_eq!(observation.get(ALLIED_UNITS, 0, 0), 0);
+}
+
+#[test]
+#[should_panic]
+fn test_map_too_big() {
+    let _ = Observation::encode(&get_map(), PLAYER_1, &[], 2, 2);
+}
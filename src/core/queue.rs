@@ -0,0 +1,227 @@
+//! This module contains the queue of moves of a player.
+//!
+//! Players can only perform one move per turn, but they can send moves faster than that: the
+//! moves are queued, and one move is taken from the queue at each turn. A `MoveTo` action queues
+//! all the moves of a path at once. These moves remember which path they belong to and its final
+//! destination, so that when one of them becomes invalid (because the path crosses a tile that
+//! turned out to be an obstacle for example), the rest of the path can be planned again.
+//!
+//! Players get the state of their queue in each update (see `QueueState`), so that they know
+//! which moves are still pending, and whether the move taken from the queue was performed. They
+//! can also edit their queue: undo the last move, remove the moves from a tile, insert a move at
+//! the front, or replace the whole queue (see `MoveQueue::apply`).
+use std::collections::VecDeque;
+
+use super::common::{Action, Move, PlayerId};
+use super::game::Game;
+
+/// A move waiting in a queue.
+#[derive(Copy, Clone, Debug)]
+pub struct QueuedMove {
+    pub mv: Move,
+    /// If the move is a step of a path, the last tile of the path
+    pub target: Option<usize>,
+    /// If the move is a step of a path, the identifier of the path. Each path gets its own
+    /// identifier, even if it goes to the same tile as another one.
+    pub path: Option<usize>,
+}
+
+/// What happened to a move taken from a queue.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveStatus {
+    /// The move was performed
+    Performed,
+    /// The move was invalid, and was ignored by the game
+    Rejected,
+}
+
+/// The move taken from a queue at the last turn.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct ConsumedMove {
+    #[serde(flatten)]
+    pub mv: Move,
+    pub status: MoveStatus,
+}
+
+/// The state of a queue, as sent to the player in its updates.
+#[derive(Clone, Debug, Serialize)]
+pub struct QueueState {
+    /// The moves that are still in the queue, starting with the next one
+    pub pending: Vec<Move>,
+    /// The move that was taken from the queue at the last turn, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consumed: Option<ConsumedMove>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MoveQueue {
+    moves: VecDeque<QueuedMove>,
+    /// The move that was taken from the queue at the last turn
+    consumed: Option<ConsumedMove>,
+    /// Identifier of the next path queued
+    next_path: usize,
+}
+
+impl MoveQueue {
+    pub fn new() -> Self {
+        MoveQueue {
+            moves: VecDeque::new(),
+            consumed: None,
+            next_path: 0,
+        }
+    }
+
+    /// Add a move at the end of the queue.
+    pub fn push(&mut self, mv: Move) {
+        self.moves.push_back(QueuedMove {
+            mv,
+            target: None,
+            path: None,
+        });
+    }
+
+    /// Add the moves of the shortest path between the given tiles at the end of the queue (see
+    /// `Game::path`). Return `false` if there is no path, in which case nothing is queued.
+    pub fn push_path(&mut self, game: &Game, player: PlayerId, from: usize, to: usize) -> bool {
+        match game.path(player, from, to) {
+            Some(path) => {
+                let id = self.next_path;
+                self.next_path += 1;
+                self.moves.extend(path.into_iter().map(|mv| QueuedMove {
+                    mv,
+                    target: Some(to),
+                    path: Some(id),
+                }));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add a move at the front of the queue, so that it is the next move taken from it.
+    pub fn push_front(&mut self, mv: Move) {
+        self.moves.push_front(QueuedMove {
+            mv,
+            target: None,
+            path: None,
+        });
+    }
+
+    /// Remove the last move of the queue, and return it.
+    pub fn pop_back(&mut self) -> Option<Move> {
+        self.moves.pop_back().map(|queued| queued.mv)
+    }
+
+    /// Remove all the moves that start from the given tile.
+    pub fn remove_from(&mut self, tile: usize) {
+        self.moves.retain(|queued| queued.mv.from != tile);
+    }
+
+    /// Replace all the moves of the queue by the given moves.
+    pub fn replace(&mut self, moves: &[Move]) {
+        self.moves.clear();
+        self.moves.extend(moves.iter().map(|mv| QueuedMove {
+            mv: *mv,
+            target: None,
+            path: None,
+        }));
+    }
+
+    /// Keep only the first `len` moves of the queue.
+    pub fn truncate(&mut self, len: usize) {
+        self.moves.truncate(len);
+    }
+
+    /// Remove all the moves from the queue.
+    pub fn clear(&mut self) {
+        self.moves.clear();
+    }
+
+    /// Update the queue according to an action of the given player. `Resign` actions do not
+    /// change the queue: they must be handled by the caller. Return `false` if the action could
+    /// not be applied, which happens for `MoveTo` actions when there is no path.
+    pub fn apply(&mut self, game: &Game, player: PlayerId, action: &Action) -> bool {
+        match *action {
+            Action::Move(mv) => self.push(mv),
+            Action::MoveTo { from, to } => return self.push_path(game, player, from, to),
+            Action::MoveFirst(mv) => self.push_front(mv),
+            Action::UndoMove => {
+                let _ = self.pop_back();
+            }
+            Action::ClearMovesFrom { tile } => self.remove_from(tile),
+            Action::ReplaceMoves { ref moves } => self.replace(moves),
+            Action::CancelMoves => self.clear(),
+            Action::Resign => {}
+        }
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    /// Return an iterator over the queued moves, starting with the next one.
+    pub fn iter(&self) -> impl Iterator<Item = &QueuedMove> {
+        self.moves.iter()
+    }
+
+    /// Take the next move from the queue. If that move is a step of a path and is not valid
+    /// anymore, the rest of the path is planned again from the step's source tile. If there is no
+    /// path anymore, the rest of the path is dropped, and the invalid move is returned anyway so
+    /// that it gets rejected by the game like any other invalid move. The new path can be longer
+    /// than the old one: callers that limit the length of the queue must truncate it again.
+    ///
+    /// The caller must then report what happened to the move with `MoveQueue::set_status`.
+    pub fn pop(&mut self, game: &Game, player: PlayerId) -> Option<Move> {
+        self.consumed = None;
+        let mut next = self.moves.pop_front()?;
+        next.mv.player = player;
+        let target = match next.target {
+            Some(target) if !game.is_legal(next.mv) => target,
+            _ => return Some(next.mv),
+        };
+
+        // Drop the rest of the path, and plan a new one.
+        while self.moves.front().is_some_and(|m| m.path == next.path) {
+            let _ = self.moves.pop_front();
+        }
+        // If the new path starts like the old one, the move is invalid for another reason (the
+        // tile does not have enough units anymore for instance), and the path is abandoned.
+        let path = match game.path(player, next.mv.from, target) {
+            Some(path) if path.first().map(|mv| mv.direction) != Some(next.mv.direction) => path,
+            _ => return Some(next.mv),
+        };
+        debug!("re-planning the path of player {} to {}", player, target);
+        for mv in path.into_iter().rev() {
+            self.moves.push_front(QueuedMove {
+                mv,
+                target: Some(target),
+                path: next.path,
+            });
+        }
+        self.moves.pop_front().map(|queued| queued.mv)
+    }
+
+    /// Report what happened to the move that was just taken from the queue.
+    pub fn set_status(&mut self, mv: Move, performed: bool) {
+        let status = if performed {
+            MoveStatus::Performed
+        } else {
+            MoveStatus::Rejected
+        };
+        self.consumed = Some(ConsumedMove { mv, status });
+    }
+
+    /// Return the state of the queue, to send to the player.
+    pub fn state(&self) -> QueueState {
+        QueueState {
+            pending: self.moves.iter().map(|queued| queued.mv).collect(),
+            consumed: self.consumed,
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::cell::RefCell;
+
+use super::common::{Action, Direction, Move, Tile};
+use super::game::Game;
+use super::grid::Grid;
+use super::map::Map;
+use super::queue::{MoveQueue, MoveStatus};
+use serde_json;
+
+const PLAYER_1: usize = 0;
+const PLAYER_2: usize = 1;
+
+/// Return a game on a map that looks like:
+///
+/// ```
+/// General1[10]  Open  Open      Open
+/// Open          Open  Mountain  Open
+/// Open          Open  Open      General2[1]
+/// ```
+fn get_game() -> Game {
+    let grid = Grid::new(
+        |index| {
+            let mut tile = Tile::new();
+            if index != 6 {
+                tile.make_open();
+            }
+            RefCell::new(tile)
+        },
+        4,
+        3,
+    );
+    let map = Map::from_grid(grid);
+    map.get_mut(0).make_general();
+    map.get_mut(0).set_units(10);
+    map.get_mut(11).make_general();
+    map.get_mut(11).set_units(1);
+    Game::from_map(vec![PLAYER_1, PLAYER_2], vec![0, 11], map)
+}
+
+fn directions(queue: &MoveQueue) -> Vec<(usize, Direction)> {
+    queue.iter().map(|m| (m.mv.from, m.mv.direction)).collect()
+}
+
+#[test]
+fn test_push_path() {
+    let game = get_game();
+    let mut queue = MoveQueue::new();
+    assert!(queue.push_path(&game, PLAYER_1, 0, 7));
+    assert_eq!(queue.len(), 4);
+    // The path goes around the mountain
+    assert_eq!(
+        directions(&queue),
+        vec![
+            (0, Direction::Right),
+            (1, Direction::Right),
+            (2, Direction::Right),
+            (3, Direction::Down),
+        ]
+    );
+
+    let mv = queue.pop(&game, PLAYER_1).unwrap();
+    assert_eq!(mv.player, PLAYER_1);
+    assert_eq!(mv.from, 0);
+    assert_eq!(queue.len(), 3);
+}
+
+#[test]
+fn test_no_path() {
+    let game = get_game();
+    let mut queue = MoveQueue::new();
+    // Destination is a mountain
+    assert!(!queue.push_path(&game, PLAYER_1, 0, 6));
+    // Destination is out of the map
+    assert!(!queue.push_path(&game, PLAYER_1, 0, 12));
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn test_replan() {
+    let mut game = get_game();
+    let mut queue = MoveQueue::new();
+    assert!(queue.push_path(&game, PLAYER_1, 0, 5));
+    assert_eq!(
+        directions(&queue),
+        vec![(0, Direction::Right), (1, Direction::Down)]
+    );
+
+    // Tile 1 becomes an obstacle: the path must go through the second line
+    game.map.get_mut(1).make_mountain();
+    let mv = queue.pop(&game, PLAYER_1).unwrap();
+    assert_eq!((mv.from, mv.direction), (0, Direction::Down));
+    assert!(game.perform_move(mv).is_some());
+    assert_eq!(directions(&queue), vec![(4, Direction::Right)]);
+}
+
+#[test]
+fn test_replan_same_target() {
+    let mut game = get_game();
+    let mut queue = MoveQueue::new();
+    assert!(queue.push_path(&game, PLAYER_1, 0, 5));
+    assert!(queue.push_path(&game, PLAYER_1, 0, 5));
+    assert_eq!(queue.len(), 4);
+
+    // Only the path being followed is planned again, not the next one to the same tile.
+    game.map.get_mut(1).make_mountain();
+    let mv = queue.pop(&game, PLAYER_1).unwrap();
+    assert_eq!((mv.from, mv.direction), (0, Direction::Down));
+    assert_eq!(
+        directions(&queue),
+        vec![
+            (4, Direction::Right),
+            (0, Direction::Right),
+            (1, Direction::Down),
+        ]
+    );
+}
+
+#[test]
+fn test_abandon_path() {
+    let game = get_game();
+    let mut queue = MoveQueue::new();
+    assert!(queue.push_path(&game, PLAYER_1, 0, 2));
+    queue.push(Move {
+        player: PLAYER_1,
+        from: 0,
+        direction: Direction::Down,
+    });
+
+    // The general does not have enough units anymore: the path is dropped, but not the moves
+    // that follow it.
+    game.map.get_mut(0).set_units(1);
+    let mv = queue.pop(&game, PLAYER_1).unwrap();
+    assert_eq!((mv.from, mv.direction), (0, Direction::Right));
+    assert!(!game.is_legal(mv));
+    assert_eq!(directions(&queue), vec![(0, Direction::Down)]);
+}
+
+#[test]
+fn test_queue_state() {
+    let mut game = get_game();
+    let mut queue = MoveQueue::new();
+    assert!(queue.push_path(&game, PLAYER_1, 0, 2));
+    assert_eq!(
+        serde_json::to_string(&queue.state()).unwrap(),
+        r#"{"pending":[{"from":0,"direction":"right"},{"from":1,"direction":"right"}]}"#
+    );
+
+    let mv = queue.pop(&game, PLAYER_1).unwrap();
+    let performed = game.perform_move(mv).is_some();
+    queue.set_status(mv, performed);
+    let state = queue.state();
+    assert_eq!(state.pending.len(), 1);
+    assert_eq!(state.consumed.unwrap().status, MoveStatus::Performed);
+    assert_eq!(
+        serde_json::to_string(&state).unwrap(),
+        r#"{"pending":[{"from":1,"direction":"right"}],"consumed":{"from":0,"direction":"right","status":"performed"}}"#
+    );
+
+    // The general only has one unit left
+    queue.push(mv);
+    let _ = queue.pop(&game, PLAYER_1).unwrap();
+    let mv = queue.pop(&game, PLAYER_1).unwrap();
+    queue.set_status(mv, game.perform_move(mv).is_some());
+    assert_eq!(queue.state().consumed.unwrap().status, MoveStatus::Rejected);
+
+    // The last consumed move is forgotten at the next turn
+    assert!(queue.pop(&game, PLAYER_1).is_none());
+    assert!(queue.state().consumed.is_none());
+}
+
+fn mv(from: usize, direction: Direction) -> Move {
+    Move {
+        player: PLAYER_1,
+        from,
+        direction,
+    }
+}
+
+#[test]
+fn test_edit_queue() {
+    let game = get_game();
+    let mut queue = MoveQueue::new();
+    assert!(queue.push_path(&game, PLAYER_1, 0, 2));
+    queue.push(mv(4, Direction::Down));
+
+    let undo: Action = serde_json::from_str(r#"{"type":"undo_move"}"#).unwrap();
+    assert!(queue.apply(&game, PLAYER_1, &undo));
+    assert_eq!(
+        directions(&queue),
+        vec![(0, Direction::Right), (1, Direction::Right)]
+    );
+
+    let first: Action =
+        serde_json::from_str(r#"{"type":"move_first","from":4,"direction":"up"}"#).unwrap();
+    assert!(queue.apply(&game, PLAYER_1, &first));
+    assert_eq!(
+        directions(&queue),
+        vec![
+            (4, Direction::Up),
+            (0, Direction::Right),
+            (1, Direction::Right),
+        ]
+    );
+
+    let clear: Action = serde_json::from_str(r#"{"type":"clear_moves_from","tile":0}"#).unwrap();
+    assert!(queue.apply(&game, PLAYER_1, &clear));
+    assert_eq!(
+        directions(&queue),
+        vec![(4, Direction::Up), (1, Direction::Right)]
+    );
+
+    let replace: Action = serde_json::from_str(
+        r#"{"type":"replace_moves","moves":[{"from":0,"direction":"down"},{"from":4,"direction":"down"}]}"#,
+    )
+    .unwrap();
+    assert!(queue.apply(&game, PLAYER_1, &replace));
+    assert_eq!(
+        directions(&queue),
+        vec![(0, Direction::Down), (4, Direction::Down)]
+    );
+
+    queue.truncate(1);
+    assert_eq!(directions(&queue), vec![(0, Direction::Down)]);
+    assert!(queue.apply(&game, PLAYER_1, &Action::CancelMoves));
+    assert!(queue.is_empty());
+    assert!(queue.pop_back().is_none());
+}
@@ -0,0 +1,349 @@
+//! This module contains a forward model of the game, meant for bots that search through possible
+//! futures.
+//!
+//! A `State` is a snapshot of a `Game` that only keeps what the game rules depend on (see
+//! `TileState`): it does not track visibility nor dirtiness, so it is cheap to clone. Turns are
+//! played with `State::apply`, which follows exactly the same rules as a live game, and can be
+//! reverted with `State::undo`.
+use super::common::{
+    Direction, InvalidMove, Move, MoveOutcome, Player, PlayerId, Teams, TileKind, TileState,
+};
+use super::game::{reinforcement, CaptureRule, Game, ResignPolicy};
+use super::grid::{Grid, Topology};
+
+/// What is needed to revert a turn, or a resignation.
+#[derive(Clone, Debug)]
+struct Change {
+    turn: usize,
+    players: Vec<(PlayerId, Option<usize>)>,
+    /// The previous state of the tiles that changed. The same tile may appear multiple times, so
+    /// they must be restored in reverse order.
+    tiles: Vec<(usize, TileState)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct State {
+    grid: Grid<TileState>,
+    turn: usize,
+    /// The players, sorted by ID, with the turn at which they were defeated, if any
+    players: Vec<(PlayerId, Option<usize>)>,
+    teams: Teams,
+    resign_policy: ResignPolicy,
+    capture_rule: CaptureRule,
+    history: Vec<Change>,
+}
+
+impl State {
+    /// Return a snapshot of the given game.
+    pub fn new(game: &Game) -> Self {
+        let mut players: Vec<(PlayerId, Option<usize>)> = game
+            .players
+            .values()
+            .map(|player| (player.id, player.defeated_at))
+            .collect();
+        players.sort();
+        State {
+            grid: game.map.states(),
+            turn: game.turn(),
+            players,
+            teams: Teams::new(game.players.values()),
+            resign_policy: game.resign_policy,
+            capture_rule: game.capture_rule,
+            history: Vec::new(),
+        }
+    }
+
+    /// Return a state built from the given tiles, which are stored line by line, on a grid with
+    /// the given topology. This is useful for bots, which do not have access to the game, and
+    /// must build the state from what they know of the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of tiles does not match the dimensions.
+    pub fn from_tiles(
+        width: usize,
+        height: usize,
+        topology: Topology,
+        tiles: &[TileState],
+        turn: usize,
+        players: &[Player],
+    ) -> Self {
+        assert_eq!(tiles.len(), width * height, "invalid number of tiles");
+        let teams = Teams::new(players);
+        let mut players: Vec<(PlayerId, Option<usize>)> = players
+            .iter()
+            .map(|player| (player.id, player.defeated_at))
+            .collect();
+        players.sort();
+        let mut grid = Grid::new(|i| tiles[i], width, height);
+        grid.set_topology(topology);
+        State {
+            grid,
+            turn,
+            players,
+            teams,
+            resign_policy: ResignPolicy::default(),
+            capture_rule: CaptureRule::default(),
+            history: Vec::new(),
+        }
+    }
+
+    /// Return the current turn number
+    pub fn turn(&self) -> usize {
+        self.turn
+    }
+
+    /// Return the grid of tiles
+    pub fn grid(&self) -> &Grid<TileState> {
+        &self.grid
+    }
+
+    /// Return the state of the tile at the given index
+    pub fn tile(&self, index: usize) -> TileState {
+        *self.grid.get(index)
+    }
+
+    /// Return the IDs of all the players, including the defeated ones
+    pub fn players(&self) -> impl Iterator<Item = PlayerId> + '_ {
+        self.players.iter().map(|(id, _)| *id)
+    }
+
+    /// Return whether the given player has been defeated. Unknown players are considered
+    /// defeated.
+    pub fn is_defeated(&self, player: PlayerId) -> bool {
+        self.players
+            .iter()
+            .find(|(id, _)| *id == player)
+            .is_none_or(|(_, defeated_at)| defeated_at.is_some())
+    }
+
+    /// Return whether the game is over, ie whether all the players that have not been defeated
+    /// yet are allies, like `Game::is_over`.
+    pub fn is_over(&self) -> bool {
+        self.teams.all_allies(
+            self.players
+                .iter()
+                .filter(|(_, d)| d.is_none())
+                .map(|(id, _)| *id),
+        )
+    }
+
+    /// Return whether the given players are allies.
+    pub fn are_allies(&self, player: PlayerId, other: PlayerId) -> bool {
+        self.teams.are_allies(player, other)
+    }
+
+    /// Return the player who won the game, if the game is over and someone won it. In team games,
+    /// this is one of the players of the winning team who have not been defeated.
+    pub fn winner(&self) -> Option<PlayerId> {
+        if !self.is_over() {
+            return None;
+        }
+        self.players
+            .iter()
+            .find(|(_, d)| d.is_none())
+            .map(|(id, _)| *id)
+    }
+
+    /// Return the number of tiles and the number of units owned by the given player.
+    pub fn land_and_army(&self, player: PlayerId) -> (usize, usize) {
+        self.grid
+            .iter()
+            .filter(|tile| tile.owner == Some(player))
+            .fold((0, 0), |(land, army), tile| {
+                (land + 1, army + tile.units as usize)
+            })
+    }
+
+    /// Return all the moves the given player can make, with their predicted outcome. See
+    /// `Game::legal_moves_with_outcome`.
+    pub fn legal_moves_with_outcome(&self, player: PlayerId) -> Vec<(Move, MoveOutcome)> {
+        if self.is_defeated(player) {
+            return Vec::new();
+        }
+        legal_moves(&self.grid, player, &self.teams, |tile| *tile)
+    }
+
+    /// Return all the moves the given player can make. See `Game::legal_moves`.
+    pub fn legal_moves(&self, player: PlayerId) -> Vec<Move> {
+        self.legal_moves_with_outcome(player)
+            .into_iter()
+            .map(|(mv, _)| mv)
+            .collect()
+    }
+
+    /// Mark the given player as defeated, and handle its tiles according to the resign policy,
+    /// like `Game::resign`.
+    pub fn resign(&mut self, player: PlayerId) {
+        let mut change = Change {
+            turn: self.turn,
+            players: self.players.clone(),
+            tiles: Vec::new(),
+        };
+        let turn = self.turn;
+        if let Some((_, defeated_at)) = self.players.iter_mut().find(|(id, _)| *id == player) {
+            if defeated_at.is_none() {
+                *defeated_at = Some(turn);
+                if self.resign_policy == ResignPolicy::Neutral {
+                    for index in 0..self.grid.len() {
+                        let tile = self.grid.get_mut(index);
+                        if tile.owner == Some(player) {
+                            change.tiles.push((index, *tile));
+                            tile.owner = None;
+                            if tile.kind == TileKind::General {
+                                tile.kind = TileKind::City;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.history.push(change);
+    }
+
+    /// Play a turn: perform the given moves in order, then increment the turn number and
+    /// reinforce the tiles. This is what happens in a live game between two updates. Like in a
+    /// live game, invalid moves and moves of defeated players are ignored. The outcome of each
+    /// move is returned, or `None` if it was ignored.
+    pub fn apply(&mut self, moves: &[Move]) -> Vec<Option<MoveOutcome>> {
+        let mut change = Change {
+            turn: self.turn,
+            players: self.players.clone(),
+            tiles: Vec::new(),
+        };
+
+        let outcomes = moves
+            .iter()
+            .map(|mv| self.perform_move(*mv, &mut change.tiles).ok())
+            .collect();
+
+        self.turn += 1;
+        if let Some(reinforce_all_tiles) = reinforcement(self.turn) {
+            // Like in `Game::reinforce`, the tiles of defeated players are not reinforced.
+            let defeated: Vec<PlayerId> = self
+                .players
+                .iter()
+                .filter(|(_, d)| d.is_some())
+                .map(|(id, _)| *id)
+                .collect();
+            for index in 0..self.grid.len() {
+                let tile = self.grid.get_mut(index);
+                let abandoned = tile.owner.is_some_and(|owner| defeated.contains(&owner));
+                if !abandoned && tile.needs_reinforcement(reinforce_all_tiles) {
+                    change.tiles.push((index, *tile));
+                    tile.units += 1;
+                }
+            }
+        }
+
+        // Like in `Game::get_update`, players that don't own any tile anymore are defeated.
+        let turn = self.turn;
+        for i in 0..self.players.len() {
+            let (player, defeated_at) = self.players[i];
+            if defeated_at.is_none() && !self.grid.iter().any(|t| t.owner == Some(player)) {
+                self.players[i].1 = Some(turn);
+            }
+        }
+
+        self.history.push(change);
+        outcomes
+    }
+
+    /// Revert the last turn played with `State::apply` (or the last resignation). Return `false`
+    /// if there is nothing to revert.
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some(change) => {
+                for (index, tile) in change.tiles.into_iter().rev() {
+                    *self.grid.get_mut(index) = tile;
+                }
+                self.turn = change.turn;
+                self.players = change.players;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Perform a move, following the same rules as `Game::perform_move`. The previous state of
+    /// the tiles that change is pushed to `changes`.
+    fn perform_move(
+        &mut self,
+        mv: Move,
+        changes: &mut Vec<(usize, TileState)>,
+    ) -> Result<MoveOutcome, InvalidMove> {
+        if self.is_defeated(mv.player) {
+            return Err(InvalidMove::SourceTileNotOwned);
+        }
+        if !self.grid.is_valid_index(mv.from) {
+            return Err(InvalidMove::FromInvalidTile);
+        }
+        let dst_idx = self
+            .grid
+            .neighbor(mv.from, mv.direction)
+            .ok_or(InvalidMove::ToInvalidTile)?;
+
+        let mut src = self.tile(mv.from);
+        let mut dst = self.tile(dst_idx);
+        if src.owner != Some(mv.player) {
+            return Err(InvalidMove::SourceTileNotOwned);
+        }
+        let outcome = src.attack(&mut dst, &self.teams)?;
+        changes.push((mv.from, self.tile(mv.from)));
+        changes.push((dst_idx, self.tile(dst_idx)));
+        *self.grid.get_mut(mv.from) = src;
+        *self.grid.get_mut(dst_idx) = dst;
+
+        // If a general was captured, all the tiles of the defeated player go to the attacker,
+        // with the number of units given by the capture rule.
+        if let MoveOutcome::GeneralCaptured(defeated_player) = outcome {
+            for index in 0..self.grid.len() {
+                let tile = self.grid.get_mut(index);
+                if tile.kind != TileKind::Mountain && tile.owner == Some(defeated_player) {
+                    changes.push((index, *tile));
+                    tile.owner = Some(mv.player);
+                    tile.units = self.capture_rule.transferred_units(tile.units);
+                }
+            }
+        }
+        Ok(outcome)
+    }
+}
+
+/// Return all the moves the given player can make on the given grid, with their predicted
+/// outcome. `state` returns the state of a tile of the grid. The checks are the ones of
+/// `Map::perform_move` and `TileState::attack`, which is used to predict the outcome, so that
+/// the rules cannot drift. Whether the player is defeated is not checked.
+pub(crate) fn legal_moves<T, F>(
+    grid: &Grid<T>,
+    player: PlayerId,
+    teams: &Teams,
+    state: F,
+) -> Vec<(Move, MoveOutcome)>
+where
+    F: Fn(&T) -> TileState,
+{
+    let mut moves = Vec::new();
+    for (from, tile) in grid.iter().enumerate() {
+        let src = state(tile);
+        if src.owner != Some(player) || src.units < 2 {
+            continue;
+        }
+        for direction in &Direction::all() {
+            let to = match grid.neighbor(from, *direction) {
+                Some(to) => to,
+                None => continue,
+            };
+            let (mut src, mut dst) = (src, state(grid.get(to)));
+            if let Ok(outcome) = src.attack(&mut dst, teams) {
+                let mv = Move {
+                    player,
+                    from,
+                    direction: *direction,
+                };
+                moves.push((mv, outcome));
+            }
+        }
+    }
+    moves
+}
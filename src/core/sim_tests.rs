@@ -0,0 +1,118 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::common::{Direction, Move, PlayerId};
+use super::game::Game;
+use super::sim::State;
+
+/// Return a random move for the given player, from one of its tiles that has enough units to
+/// move. The move may still be invalid (toward a mountain for instance).
+fn random_move(state: &State, player: PlayerId, rng: &mut StdRng) -> Option<Move> {
+    let sources: Vec<usize> = (0..state.grid().len())
+        .filter(|i| state.tile(*i).owner == Some(player) && state.tile(*i).units > 1)
+        .collect();
+    if sources.is_empty() {
+        return None;
+    }
+    Some(Move {
+        player,
+        from: sources[rng.gen_range(0, sources.len())],
+        direction: Direction::all()[rng.gen_range(0, 4)],
+    })
+}
+
+/// Check that the state and the game agree on every tile and every player.
+fn assert_same(state: &State, game: &Game) {
+    assert_eq!(state.turn(), game.turn());
+    assert_eq!(state.grid().tiles(), game.map.states().tiles());
+    for player in state.players() {
+        assert_eq!(state.is_defeated(player), game.is_defeated(player));
+        assert_eq!(state.land_and_army(player), game.land_and_army(player));
+    }
+    assert_eq!(state.winner(), game.winner());
+}
+
+#[test]
+fn test_same_results_as_game() {
+    for seed in 0..5 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut game = Game::with_seed(vec![0, 1, 2], seed).unwrap();
+        let _ = game.get_update();
+        let mut state = game.state();
+        assert_same(&state, &game);
+
+        for _ in 0..300 {
+            let moves: Vec<Move> = (0..3)
+                .filter_map(|player| random_move(&state, player, &mut rng))
+                .collect();
+            for mv in &moves {
+                let _ = game.perform_move(*mv);
+            }
+            game.incr_turn();
+            let _ = game.get_update();
+            let _ = state.apply(&moves);
+            assert_same(&state, &game);
+        }
+    }
+}
+
+#[test]
+fn test_legal_moves() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let game = Game::with_seed(vec![0, 1], 7).unwrap();
+    let mut state = game.state();
+
+    for _ in 0..30 {
+        for player in 0..2 {
+            let legal = state.legal_moves_with_outcome(player);
+            for from in 0..state.grid().len() {
+                for direction in &Direction::all() {
+                    let mv = Move {
+                        player,
+                        from,
+                        direction: *direction,
+                    };
+                    let outcome = state.clone().apply(&[mv])[0];
+                    let predicted = legal
+                        .iter()
+                        .find(|(m, _)| m.from == from && m.direction == *direction)
+                        .map(|(_, outcome)| *outcome);
+                    assert_eq!(predicted, outcome);
+                }
+            }
+        }
+        let moves: Vec<Move> = (0..2)
+            .filter_map(|player| random_move(&state, player, &mut rng))
+            .collect();
+        let _ = state.apply(&moves);
+    }
+}
+
+#[test]
+fn test_undo() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut game = Game::with_seed(vec![0, 1], 42).unwrap();
+    let _ = game.get_update();
+    let mut state = game.state();
+
+    let mut snapshots = vec![state.clone()];
+    for _ in 0..120 {
+        let moves: Vec<Move> = (0..2)
+            .filter_map(|player| random_move(&state, player, &mut rng))
+            .collect();
+        let _ = state.apply(&moves);
+        snapshots.push(state.clone());
+    }
+    state.resign(1);
+    assert!(state.is_defeated(1));
+    assert_eq!(state.winner(), Some(0));
+
+    while let Some(snapshot) = snapshots.pop() {
+        assert!(state.undo());
+        assert_eq!(state.turn(), snapshot.turn());
+        assert_eq!(state.grid().tiles(), snapshot.grid().tiles());
+        for player in snapshot.players() {
+            assert_eq!(state.is_defeated(player), snapshot.is_defeated(player));
+        }
+    }
+    assert!(!state.undo());
+}
@@ -0,0 +1,175 @@
+//! This module contains code to record games as training samples for imitation learning.
+//!
+//! At each turn, a sample is recorded for each player that is still in the game: what the player
+//! could see of the map when making its decision (the same fogged view as the one sent in the
+//! player's updates, see `core::Observation`), and the move it actually made. When the game is
+//! over, the outcome is added to all the samples, and they are exported in numpy's `.npy` format
+//! in a new directory, so that they can be loaded with `np.load`:
+//!
+//! - `observations.npy`: `uint16` planes, with shape `(samples, planes, height, width)`
+//! - `actions.npy`: `int32` move made by the player, with shape `(samples,)`. A move from the tile
+//!   at `(column, line)` in direction `d` is encoded as `(line * width + column) * 4 + d`, where
+//!   `width` is the width of the planes and `d` is `0` for right, `1` for left, `2` for up and `3`
+//!   for down. `-1` means the player did not make any valid move.
+//! - `players.npy`: `int32` ID of the player, with shape `(samples,)`
+//! - `turns.npy`: `int32` turn of the sample, with shape `(samples,)`
+//! - `outcomes.npy`: `int8` outcome for the player, with shape `(samples,)`: `1` if the player
+//!   or its team won, `-1` if it lost, and `0` if the game ended without a winner.
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use core::{max_grid_size, Game, Move, Observation, PlayerId, NB_PLANES};
+
+/// Records the samples of a single game.
+pub struct Recorder {
+    /// Directory in which a new directory is created for each game
+    directory: PathBuf,
+    /// Width and height of the planes
+    size: usize,
+    observations: Vec<u8>,
+    actions: Vec<i32>,
+    players: Vec<i32>,
+    turns: Vec<i32>,
+}
+
+impl Recorder {
+    /// Return a new recorder for a game with the given number of players. The samples will be
+    /// exported in a new directory under `directory`.
+    pub fn new(directory: PathBuf, nb_players: usize) -> Self {
+        Recorder {
+            directory,
+            size: max_grid_size(nb_players),
+            observations: Vec::new(),
+            actions: Vec::new(),
+            players: Vec::new(),
+            turns: Vec::new(),
+        }
+    }
+
+    /// Return what the given player currently sees of the map.
+    pub fn observe(&self, game: &Game, player: PlayerId) -> Observation {
+        game.observe(player, self.size, self.size)
+    }
+
+    /// Record a sample for the given player: the observation it made its decision on, and the
+    /// move it made, if any.
+    pub fn record(
+        &mut self,
+        game: &Game,
+        player: PlayerId,
+        observation: &Observation,
+        mv: Option<Move>,
+    ) {
+        let action = mv.map_or(-1, |mv| {
+            let (column, line) = (mv.from % game.width(), mv.from / game.width());
+            ((line * self.size + column) * 4 + mv.direction as usize) as i32
+        });
+        self.observations.extend(observation.to_bytes());
+        self.actions.push(action);
+        self.players.push(player as i32);
+        self.turns.push(game.turn() as i32);
+    }
+
+    /// Export all the samples in a new directory, with the outcome of the game: `winners` are
+    /// the players who won, and is empty if nobody did. Return the path of the new directory.
+    pub fn export(&self, winners: &[PlayerId]) -> io::Result<PathBuf> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let directory = self.directory.join(format!(
+            "game-{}-{:09}",
+            timestamp.as_secs(),
+            timestamp.subsec_nanos()
+        ));
+        fs::create_dir_all(&directory)?;
+
+        let nb_samples = self.actions.len();
+        let outcomes: Vec<u8> = self
+            .players
+            .iter()
+            .map(|player| {
+                if winners.is_empty() {
+                    0
+                } else if winners.iter().any(|winner| *winner as i32 == *player) {
+                    1
+                } else {
+                    -1i8 as u8
+                }
+            })
+            .collect();
+
+        write_npy(
+            &directory.join("observations.npy"),
+            "<u2",
+            &[nb_samples, NB_PLANES, self.size, self.size],
+            &self.observations,
+        )?;
+        write_npy(
+            &directory.join("actions.npy"),
+            "<i4",
+            &[nb_samples],
+            &to_bytes(&self.actions),
+        )?;
+        write_npy(
+            &directory.join("players.npy"),
+            "<i4",
+            &[nb_samples],
+            &to_bytes(&self.players),
+        )?;
+        write_npy(
+            &directory.join("turns.npy"),
+            "<i4",
+            &[nb_samples],
+            &to_bytes(&self.turns),
+        )?;
+        write_npy(
+            &directory.join("outcomes.npy"),
+            "|i1",
+            &[nb_samples],
+            &outcomes,
+        )?;
+        Ok(directory)
+    }
+}
+
+/// Return the given integers as little-endian bytes.
+fn to_bytes(values: &[i32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes().to_vec())
+        .collect()
+}
+
+/// Write an array in numpy's `.npy` format (version 1.0). `data` must already be encoded
+/// according to the given numpy type descriptor (`dtype`).
+pub(crate) fn write_npy(path: &Path, dtype: &str, shape: &[usize], data: &[u8]) -> io::Result<()> {
+    let shape = match shape.len() {
+        1 => format!("({},)", shape[0]),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        dtype, shape
+    );
+    // The header is padded with spaces and terminated by a newline, so that the data starts at a
+    // multiple of 64 bytes. The magic string, version and header length take 10 bytes.
+    let padding = 64 - (10 + header.len() + 1) % 64;
+    header.push_str(&" ".repeat(padding % 64));
+    header.push('\n');
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"\x93NUMPY\x01\x00")?;
+    file.write_all(&(header.len() as u16).to_le_bytes())?;
+    file.write_all(header.as_bytes())?;
+    file.write_all(data)?;
+    file.flush()
+}
@@ -0,0 +1,95 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use core::{Game, NB_PLANES};
+use dataset::{write_npy, Recorder};
+
+/// Return a new empty directory for the given test.
+fn get_directory(test: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("generals-{}-{}", test, process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+#[test]
+fn test_write_npy() {
+    let directory = get_directory("write-npy");
+    let path = directory.join("array.npy");
+    let data = [1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0];
+    write_npy(&path, "<i4", &[3], &data).unwrap();
+    let bytes = fs::read(&path).unwrap();
+
+    // Magic string, version 1.0, and header length (little endian)
+    assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+    assert_eq!(&bytes[8..10], &[118, 0]);
+    // The header is padded with spaces, so that the data starts at a multiple of 64 bytes.
+    let header = "{'descr': '<i4', 'fortran_order': False, 'shape': (3,), }";
+    let expected = format!("{}{}\n", header, " ".repeat(60));
+    assert_eq!(&bytes[10..128], expected.as_bytes());
+    assert_eq!(&bytes[128..], &data);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_write_npy_shape() {
+    let directory = get_directory("write-npy-shape");
+    let path = directory.join("array.npy");
+    write_npy(&path, "<u2", &[2, 3, 4], &[0; 48]).unwrap();
+    let bytes = fs::read(&path).unwrap();
+
+    let header_len = usize::from(bytes[8]) + 256 * usize::from(bytes[9]);
+    assert_eq!((10 + header_len) % 64, 0);
+    let header = String::from_utf8(bytes[10..10 + header_len].to_vec()).unwrap();
+    assert!(header.starts_with("{'descr': '<u2', 'fortran_order': False, 'shape': (2, 3, 4), }"));
+    assert!(header.ends_with(" \n"));
+    assert_eq!(bytes.len(), 10 + header_len + 48);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_recorder() {
+    let directory = get_directory("recorder");
+    let mut game = Game::with_seed(vec![0, 1], 0).unwrap();
+    let mut recorder = Recorder::new(directory.clone(), 2);
+    for player in 0..2 {
+        let observation = recorder.observe(&game, player);
+        recorder.record(&game, player, &observation, None);
+    }
+    game.incr_turn();
+    let observation = recorder.observe(&game, 0);
+    recorder.record(&game, 0, &observation, None);
+
+    let export = recorder.export(&[0]).unwrap();
+    let size = observation.shape().1;
+    let read = |name: &str| {
+        let bytes = fs::read(export.join(name)).unwrap();
+        let header_len = usize::from(bytes[8]) + 256 * usize::from(bytes[9]);
+        let header = String::from_utf8(bytes[10..10 + header_len].to_vec()).unwrap();
+        (header, bytes[10 + header_len..].to_vec())
+    };
+
+    // Each file is an array of one value per sample.
+    let (header, data) = read("observations.npy");
+    let shape = format!("'shape': (3, {}, {}, {})", NB_PLANES, size, size);
+    assert!(header.contains("'descr': '<u2'") && header.contains(&shape));
+    assert_eq!(data.len(), 3 * NB_PLANES * size * size * 2);
+
+    let (header, data) = read("actions.npy");
+    assert!(header.contains("'descr': '<i4'") && header.contains("'shape': (3,)"));
+    assert_eq!(data, [255; 12]);
+
+    let (_, data) = read("players.npy");
+    assert_eq!(data, [0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+    let (_, data) = read("turns.npy");
+    assert_eq!(data, [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0]);
+    let (header, data) = read("outcomes.npy");
+    assert!(header.contains("'descr': '|i1'"));
+    assert_eq!(data, [1, 255, 1]);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
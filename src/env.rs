@@ -0,0 +1,396 @@
+//! This module contains a gym-like environment to train bots with reinforcement learning.
+//!
+//! The environment drives a `Game` without any timer: a turn is played each time `Env::step` is
+//! called, with one action per agent. Agents are the players of the game, and are numbered from
+//! `0` to `nb_agents - 1`.
+//!
+//! The environment can be driven through a simple line-based JSON protocol (see `Env::serve`),
+//! which makes it usable from any language. Each request is a single line, and each request gets
+//! a single line as response:
+//!
+//! ```text
+//! >>> {"type":"reset","seed":42}
+//! <<< {"observations":[...]}
+//! >>> {"type":"step","actions":[{"type":"move","from":42,"direction":"left"},null]}
+//! <<< {"observations":[...],"rewards":[0.1,-0.1],"dones":[false,false],"turn":1}
+//! ```
+//!
+//! By default, the observations are the updates filtered for each agent, exactly like the ones
+//! sent to the websocket clients. If the `reset` request contains `"observation":"planes"`, the
+//! observations are the agents' views of the map encoded as planes (see `core::Observation`):
+//! `{"shape":[planes,height,width],"data":"..."}`, where `data` is the base64 encoding of
+//! `Observation::to_bytes`.
+//!
+//! If a request cannot be processed, the response is `{"error":"..."}`.
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use rand::{thread_rng, Rng};
+use serde::de::DeserializeOwned;
+use serde::Serializer;
+use serde_json;
+
+use core::{
+    max_grid_size, Action, Game, GeneratorError, Move, MoveOutcome, Observation, PlayerId, Update,
+};
+
+/// Weights used to compute the rewards of the agents at each step.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Rewards {
+    /// Reward for each tile gained (or penalty for each tile lost) during the step
+    pub land: f64,
+    /// Reward for each unit gained (or penalty for each unit lost) during the step
+    pub army: f64,
+    /// Reward for capturing a general
+    pub capture: f64,
+    /// Reward for winning the game
+    pub win: f64,
+    /// Reward for being defeated. This should usually be negative.
+    pub defeat: f64,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Rewards {
+            land: 0.1,
+            army: 0.01,
+            capture: 1.0,
+            win: 10.0,
+            defeat: -10.0,
+        }
+    }
+}
+
+/// The type of observations returned by the environment.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ObservationKind {
+    /// The update filtered for the agent
+    Update,
+    /// The agent's view of the map, encoded as planes as big as the biggest possible map
+    Planes,
+}
+
+/// What an agent observes after a reset or a step.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum AgentObservation {
+    Update(Update),
+    Planes(#[serde(serialize_with = "serialize_planes")] Observation),
+}
+
+/// What the environment returns after each step.
+#[derive(Serialize)]
+pub struct Step {
+    /// The observations of each agent
+    pub observations: Vec<AgentObservation>,
+    /// The reward of each agent for this step
+    pub rewards: Vec<f64>,
+    /// Whether the episode is over for each agent. The episode is over for an agent if it has been
+    /// defeated, or if the whole game is over.
+    pub dones: Vec<bool>,
+    /// Turn reached after this step
+    pub turn: usize,
+}
+
+pub struct Env {
+    nb_agents: usize,
+    max_turns: usize,
+    rewards: Rewards,
+    observation: ObservationKind,
+    game: Option<Game>,
+    /// Number of tiles and units owned by each agent at the end of the previous step
+    stats: Vec<(usize, usize)>,
+}
+
+impl Env {
+    /// Return a new environment for the given number of agents. `Env::reset` must be called
+    /// before the first step.
+    pub fn new(nb_agents: usize, max_turns: usize, rewards: Rewards) -> Self {
+        Env {
+            nb_agents,
+            max_turns,
+            rewards,
+            observation: ObservationKind::Update,
+            game: None,
+            stats: Vec::new(),
+        }
+    }
+
+    /// Start a new episode, and return the first observation of each agent. The same seed always
+    /// produces the same map. Return an error if the map cannot be generated.
+    pub fn reset(&mut self, seed: u64) -> Result<Vec<AgentObservation>, EnvError> {
+        let mut game = Game::with_seed((0..self.nb_agents).collect(), seed)?;
+        let update = game.get_update();
+        self.stats = (0..self.nb_agents)
+            .map(|agent| game.land_and_army(agent))
+            .collect();
+        let observations = self.observations(&game, &update);
+        self.game = Some(game);
+        Ok(observations)
+    }
+
+    /// Set the type of observations returned by the environment.
+    pub fn set_observation_kind(&mut self, kind: ObservationKind) {
+        self.observation = kind;
+    }
+
+    /// Play one turn, with the given action for each agent. Agents that are done, and agents for
+    /// which the action is `None` don't do anything this turn. Moves are performed in the agents
+    /// order, and invalid moves are ignored.
+    pub fn step(&mut self, actions: &[Option<Action>]) -> Result<Step, EnvError> {
+        if actions.len() != self.nb_agents {
+            return Err(EnvError::InvalidActions(actions.len()));
+        }
+        let mut game = self.game.take().ok_or(EnvError::NotStarted)?;
+        let step = self.play(&mut game, actions);
+        self.game = Some(game);
+        step
+    }
+
+    fn play(&mut self, game: &mut Game, actions: &[Option<Action>]) -> Result<Step, EnvError> {
+        if game.is_over() || game.turn() >= self.max_turns {
+            return Err(EnvError::EpisodeOver);
+        }
+
+        let mut rewards = vec![0.0; self.nb_agents];
+        let was_defeated: Vec<bool> = (0..self.nb_agents)
+            .map(|agent| game.is_defeated(agent))
+            .collect();
+
+        for (agent, action) in actions.iter().enumerate() {
+            if was_defeated[agent] {
+                continue;
+            }
+            // There is no queue in the environment: only the first move of a path or of a list
+            // of moves is performed, and the actions that edit the queue do nothing.
+            let mv = match *action {
+                Some(Action::Move(mut mv)) | Some(Action::MoveFirst(mut mv)) => {
+                    mv.player = agent;
+                    Some(mv)
+                }
+                Some(Action::ReplaceMoves { ref moves }) => moves.first().map(|mv| Move {
+                    player: agent,
+                    ..*mv
+                }),
+                Some(Action::MoveTo { from, to }) => game
+                    .path(agent, from, to)
+                    .and_then(|path| path.first().cloned()),
+                Some(Action::Resign) => {
+                    game.resign(agent);
+                    None
+                }
+                Some(Action::CancelMoves)
+                | Some(Action::UndoMove)
+                | Some(Action::ClearMovesFrom { .. })
+                | None => None,
+            };
+            if let Some(mv) = mv {
+                if let Some(MoveOutcome::GeneralCaptured(_)) = game.perform_move(mv) {
+                    rewards[agent] += self.rewards.capture;
+                }
+            }
+        }
+        game.incr_turn();
+        let update = game.get_update();
+
+        let game_over = game.is_over() || game.turn() >= self.max_turns;
+        let winner = game.winner();
+        let mut dones = Vec::with_capacity(self.nb_agents);
+        for agent in 0..self.nb_agents {
+            let (land, army) = game.land_and_army(agent);
+            let (prev_land, prev_army) = self.stats[agent];
+            rewards[agent] += (land as f64 - prev_land as f64) * self.rewards.land
+                + (army as f64 - prev_army as f64) * self.rewards.army;
+            self.stats[agent] = (land, army);
+
+            let defeated = game.is_defeated(agent);
+            if defeated && !was_defeated[agent] {
+                rewards[agent] += self.rewards.defeat;
+            }
+            if winner == Some(agent) {
+                rewards[agent] += self.rewards.win;
+            }
+            dones.push(game_over || defeated);
+        }
+
+        Ok(Step {
+            observations: self.observations(game, &update),
+            rewards,
+            dones,
+            turn: game.turn(),
+        })
+    }
+
+    fn observations(&self, game: &Game, update: &Update) -> Vec<AgentObservation> {
+        let size = max_grid_size(self.nb_agents);
+        (0..self.nb_agents)
+            .map(|agent: PlayerId| match self.observation {
+                ObservationKind::Update => AgentObservation::Update(update.filtered(agent)),
+                ObservationKind::Planes => {
+                    AgentObservation::Planes(game.observe(agent, size, size))
+                }
+            })
+            .collect()
+    }
+
+    /// Serve the environment over the JSON lines protocol described in the module documentation,
+    /// until the input is closed.
+    pub fn serve<R: BufRead, W: Write>(&mut self, input: R, output: W) -> io::Result<()> {
+        serve(input, output, |request| self.handle_request(request))
+    }
+
+    fn handle_request(&mut self, request: Request) -> Result<serde_json::Value, EnvError> {
+        match request {
+            Request::Reset {
+                seed,
+                rewards,
+                observation,
+            } => {
+                if let Some(rewards) = rewards {
+                    self.rewards = rewards;
+                }
+                if let Some(observation) = observation {
+                    self.observation = observation;
+                }
+                let seed = seed.unwrap_or_else(|| thread_rng().gen());
+                Ok(json!({ "observations": self.reset(seed)? }))
+            }
+            Request::Step { actions } => Ok(serde_json::to_value(self.step(&actions)?)?),
+        }
+    }
+}
+
+/// Read JSON requests line by line from `input`, and write the response to each request on
+/// `output`, until the input is closed.
+pub(crate) fn serve<R, W, T, F>(input: R, mut output: W, mut handle: F) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write,
+    T: DeserializeOwned,
+    F: FnMut(T) -> Result<serde_json::Value, EnvError>,
+{
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str(&line) {
+            Ok(request) => handle(request),
+            Err(e) => Err(EnvError::InvalidRequest(e.to_string())),
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => json!({ "error": e.to_string() }),
+        };
+        serde_json::to_writer(&mut output, &response)?;
+        output.write_all(b"\n")?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+fn serialize_planes<S: Serializer>(observation: &Observation, s: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeStruct;
+    let mut planes = s.serialize_struct("Planes", 2)?;
+    planes.serialize_field("shape", &observation.shape())?;
+    planes.serialize_field("data", &base64(&observation.to_bytes()))?;
+    planes.end()
+}
+
+/// Return the standard base64 encoding of the given bytes.
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// A request of the JSON lines protocol.
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "lowercase")]
+enum Request {
+    /// Start a new episode. If no seed is given, a random one is used.
+    Reset {
+        seed: Option<u64>,
+        rewards: Option<Rewards>,
+        observation: Option<ObservationKind>,
+    },
+    /// Play one turn
+    Step { actions: Vec<Option<Action>> },
+}
+
+#[derive(Debug)]
+pub enum EnvError {
+    /// A step was requested before the first reset
+    NotStarted,
+    /// A step was requested after the end of the episode
+    EpisodeOver,
+    /// The number of actions does not match the number of agents
+    InvalidActions(usize),
+    /// The request could not be parsed
+    InvalidRequest(String),
+    /// The map of a new episode could not be generated
+    Generator(GeneratorError),
+}
+
+impl From<GeneratorError> for EnvError {
+    fn from(err: GeneratorError) -> Self {
+        EnvError::Generator(err)
+    }
+}
+
+impl From<serde_json::Error> for EnvError {
+    fn from(err: serde_json::Error) -> Self {
+        EnvError::InvalidRequest(err.to_string())
+    }
+}
+
+impl Error for EnvError {
+    fn description(&self) -> &str {
+        match *self {
+            EnvError::NotStarted => "the environment must be reset before the first step",
+            EnvError::EpisodeOver => "the episode is over, the environment must be reset",
+            EnvError::InvalidActions(_) => {
+                "the number of actions does not match the number of agents"
+            }
+            EnvError::InvalidRequest(_) => "invalid request",
+            EnvError::Generator(ref e) => e.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            EnvError::Generator(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EnvError::InvalidActions(n) => write!(f, "{} (got {} actions)", self.description(), n),
+            EnvError::InvalidRequest(e) => write!(f, "{}: {}", self.description(), e),
+            EnvError::Generator(e) => write!(f, "{}", e),
+            _ => write!(f, "{}", self.description()),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use serde_json;
+
+use core::{Action, NB_PLANES};
+use env::{base64, AgentObservation, Env, EnvError, ObservationKind, Rewards};
+
+#[test]
+fn test_not_started() {
+    let mut env = Env::new(2, 10, Rewards::default());
+    match env.step(&[None, None]) {
+        Err(EnvError::NotStarted) => {}
+        step => panic!("unexpected step {:?}", step.map(|step| step.turn)),
+    }
+}
+
+#[test]
+fn test_reset() {
+    let mut env = Env::new(2, 10, Rewards::default());
+    let observations = env.reset(42).unwrap();
+    assert_eq!(observations.len(), 2);
+    for (agent, observation) in observations.iter().enumerate() {
+        match observation {
+            AgentObservation::Update(update) => {
+                assert_eq!(update.turn(), 0);
+                assert_eq!(update.player(), Some(agent));
+            }
+            AgentObservation::Planes(_) => panic!("unexpected planes"),
+        }
+    }
+
+    // The same seed produces the same map.
+    let _ = env.step(&[None, None]).unwrap();
+    let tiles = |observations: &[AgentObservation]| match observations[0] {
+        AgentObservation::Update(ref update) => serde_json::to_string(update.tiles()).unwrap(),
+        AgentObservation::Planes(_) => panic!("unexpected planes"),
+    };
+    assert_eq!(tiles(&observations), tiles(&env.reset(42).unwrap()));
+}
+
+#[test]
+fn test_planes() {
+    let mut env = Env::new(2, 10, Rewards::default());
+    env.set_observation_kind(ObservationKind::Planes);
+    let _ = env.reset(42).unwrap();
+    let step = env.step(&[None, None]).unwrap();
+    for observation in &step.observations {
+        match observation {
+            AgentObservation::Planes(planes) => {
+                let (nb_planes, height, width) = planes.shape();
+                assert_eq!(nb_planes, NB_PLANES);
+                assert_eq!(height, width);
+            }
+            AgentObservation::Update(_) => panic!("unexpected update"),
+        }
+    }
+}
+
+#[test]
+fn test_step() {
+    let mut env = Env::new(2, 10, Rewards::default());
+    let _ = env.reset(42).unwrap();
+    match env.step(&[None]) {
+        Err(EnvError::InvalidActions(1)) => {}
+        step => panic!("unexpected step {:?}", step.map(|step| step.turn)),
+    }
+
+    // Generals are reinforced every other turn.
+    let step = env.step(&[None, None]).unwrap();
+    assert_eq!(step.turn, 1);
+    assert_eq!(step.dones, vec![false, false]);
+    assert_eq!(step.rewards, vec![0.0, 0.0]);
+    let step = env.step(&[None, None]).unwrap();
+    assert_eq!(step.turn, 2);
+    assert_eq!(step.rewards, vec![0.01, 0.01]);
+}
+
+#[test]
+fn test_max_turns() {
+    let mut env = Env::new(2, 3, Rewards::default());
+    let _ = env.reset(42).unwrap();
+    for turn in 1..=3 {
+        let step = env.step(&[None, None]).unwrap();
+        assert_eq!(step.turn, turn);
+        assert_eq!(step.dones, vec![turn == 3; 2]);
+    }
+    match env.step(&[None, None]) {
+        Err(EnvError::EpisodeOver) => {}
+        step => panic!("unexpected step {:?}", step.map(|step| step.turn)),
+    }
+
+    // A new episode can be started after the end of the previous one.
+    let _ = env.reset(43).unwrap();
+    assert_eq!(env.step(&[None, None]).unwrap().turn, 1);
+}
+
+#[test]
+fn test_resign() {
+    let rewards = Rewards::default();
+    let mut env = Env::new(2, 10, rewards);
+    let _ = env.reset(42).unwrap();
+    let step = env.step(&[Some(Action::Resign), None]).unwrap();
+    assert_eq!(step.dones, vec![true, true]);
+    assert!(step.rewards[0] <= rewards.defeat);
+    assert!(step.rewards[1] >= rewards.win);
+    match env.step(&[None, None]) {
+        Err(EnvError::EpisodeOver) => {}
+        step => panic!("unexpected step {:?}", step.map(|step| step.turn)),
+    }
+}
+
+#[test]
+fn test_base64() {
+    // Test vectors of RFC 4648, which cover all the padding cases
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foo"), "Zm9v");
+    assert_eq!(base64(b"foob"), "Zm9vYg==");
+    assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+    assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    // All the characters of the alphabet
+    assert_eq!(base64(&[0x00, 0x10, 0x83]), "ABCD");
+    assert_eq!(base64(&[0xfb, 0xff, 0xbf]), "+/+/");
+    assert_eq!(base64(&[0xff, 0xff]), "//8=");
+}
+
+#[test]
+fn test_serve() {
+    // The requests of the module documentation
+    let input = concat!(
+        r#"{"type":"reset","seed":42}"#,
+        "\n",
+        r#"{"type":"step","actions":[{"type":"move","from":42,"direction":"left"},null]}"#,
+        "\n",
+        r#"{"type":"step","actions":[null]}"#,
+        "\n",
+        r#"{"type":"jump"}"#,
+        "\n",
+    );
+    let mut output = Vec::new();
+    let mut env = Env::new(2, 10, Rewards::default());
+    env.serve(input.as_bytes(), &mut output).unwrap();
+
+    let responses: Vec<serde_json::Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(responses.len(), 4);
+    assert_eq!(responses[0]["observations"].as_array().unwrap().len(), 2);
+    assert!(responses[0].get("error").is_none());
+    assert_eq!(responses[1]["turn"], 1);
+    assert_eq!(responses[1]["dones"], json!([false, false]));
+    assert_eq!(responses[1]["observations"].as_array().unwrap().len(), 2);
+    assert!(responses[2]["error"]
+        .as_str()
+        .unwrap()
+        .contains("got 1 actions"));
+    assert!(responses[3]["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("invalid request"));
+}
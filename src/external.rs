@@ -0,0 +1,134 @@
+//! This module contains code to run bots as external processes.
+//!
+//! An external bot is a local executable that plays through its standard input and output. At
+//! each turn, it receives the `Update` filtered for its player as a single line of JSON on its
+//! stdin, and must answer with a single line on its stdout: either a JSON action (exactly like
+//! the ones sent by the websocket clients, including the optional `turn` and `seq` fields, see
+//! `StampedAction`), or an empty line (or `null`) if it does not want to do anything this turn.
+//!
+//! A bot that does not answer within its time budget, that crashes or that closes its stdout is
+//! killed and considered as having resigned.
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use serde_json;
+
+use bot::{Bot, BotError};
+use core::{StampedAction, Update};
+
+/// Default time a bot has to answer an update.
+pub const DEFAULT_BUDGET: Duration = Duration::from_millis(400);
+
+/// A bot running as a child process.
+pub struct ExternalBot {
+    /// The command used to start the bot, used for logging.
+    command: String,
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines read from the bot's stdout. They are read by a dedicated thread, so that we can wait
+    /// for them with a timeout.
+    lines: mpsc::Receiver<io::Result<String>>,
+    /// Maximum time the bot has to answer an update.
+    budget: Duration,
+}
+
+impl ExternalBot {
+    /// Start the given command. The command is split on whitespaces: the first word is the
+    /// executable and the next ones are its arguments.
+    pub fn spawn(command: &str, budget: Duration) -> Result<Self, BotError> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or_else(|| {
+            BotError::Io(io::Error::new(io::ErrorKind::InvalidInput, "empty command"))
+        })?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        info!("spawned bot \"{}\" (pid {})", command, child.id());
+
+        let stdin = child.stdin.take().expect("bot stdin is not piped");
+        let stdout = child.stdout.take().expect("bot stdout is not piped");
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(ExternalBot {
+            command: command.to_string(),
+            child,
+            stdin,
+            lines: rx,
+            budget,
+        })
+    }
+
+    /// Send an update to the bot and wait for its answer. If the bot does not answer in time, if
+    /// it crashed, or if it closed its stdout, it is killed and an error is returned.
+    pub fn play(&mut self, update: &Update) -> Result<Option<StampedAction>, BotError> {
+        let result = self.send(update).and_then(|()| self.receive());
+        if let Err(ref e) = result {
+            warn!("bot \"{}\" failed: {}. Killing it.", self.command, e);
+            self.kill();
+        }
+        result
+    }
+
+    fn send(&mut self, update: &Update) -> Result<(), BotError> {
+        let mut msg = serde_json::to_string(update).map_err(io::Error::from)?;
+        msg.push('\n');
+        self.stdin.write_all(msg.as_bytes())?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Option<StampedAction>, BotError> {
+        let line = match self.lines.recv_timeout(self.budget) {
+            Ok(line) => line?,
+            Err(RecvTimeoutError::Timeout) => return Err(BotError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => return Err(BotError::Exited),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        match serde_json::from_str(line) {
+            Ok(action) => Ok(action),
+            Err(e) => {
+                // Like for websocket clients, invalid messages are just ignored.
+                error!(
+                    "Could not deserialize message from bot \"{}\": {} (err: {})",
+                    self.command, line, e
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    /// Kill the bot process. This is a no-op if the process already exited.
+    pub fn kill(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+        }
+        let _ = self.child.wait();
+    }
+}
+
+impl Bot for ExternalBot {
+    fn play(&mut self, update: &Update) -> Result<Option<StampedAction>, BotError> {
+        ExternalBot::play(self, update)
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
@@ -0,0 +1,68 @@
+use std::env;
+use std::fs;
+use std::process;
+use std::time::Duration;
+
+use serde_json;
+
+use bot::BotError;
+use core::{Game, Update};
+use external::ExternalBot;
+
+/// Write the given shell script to a temporary file, and return the command that runs it, to
+/// be used as a bot specification.
+pub fn script_bot(name: &str, script: &str) -> String {
+    let path = env::temp_dir().join(format!("generals-{}-{}.sh", name, process::id()));
+    fs::write(&path, script).unwrap();
+    format!("sh {}", path.display())
+}
+
+/// Return the first update of a new game, filtered for the first player.
+fn get_update() -> Update {
+    Game::with_seed(vec![0, 1], 0)
+        .unwrap()
+        .get_update()
+        .filtered(0)
+}
+
+fn spawn(name: &str, script: &str) -> ExternalBot {
+    ExternalBot::spawn(&script_bot(name, script), Duration::from_millis(500)).unwrap()
+}
+
+#[test]
+fn test_move() {
+    let action = r#"{"type":"move","from":3,"direction":"left","seq":7}"#;
+    let mut bot = spawn(
+        "move",
+        &format!("while read update; do echo '{}'; done", action),
+    );
+    for _ in 0..2 {
+        let stamped = bot.play(&get_update()).unwrap().unwrap();
+        assert_eq!(serde_json::to_string(&stamped).unwrap(), action);
+    }
+}
+
+#[test]
+fn test_no_action() {
+    // Empty lines, `null` and invalid messages all mean that the bot does nothing this turn.
+    let mut bot = spawn(
+        "no-action",
+        "read update; echo; read update; echo null; read update; echo garbage; read update",
+    );
+    for _ in 0..3 {
+        assert!(bot.play(&get_update()).unwrap().is_none());
+    }
+    match bot.play(&get_update()) {
+        Err(BotError::Exited) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn test_timeout() {
+    let mut bot = spawn("timeout", "read update; sleep 5");
+    match bot.play(&get_update()) {
+        Err(BotError::Timeout) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
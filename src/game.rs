@@ -1,5 +1,8 @@
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use std::iter::FromIterator;
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
 use futures::stream::Stream;
@@ -8,18 +11,86 @@ use futures::{Future, Poll};
 use tokio_timer::{self, Interval};
 
 use connection::ConnectionProxy;
-use core::{Game, PlayerId};
+use core::{CaptureRule, Game, GeneratorConfig, GeneratorError, MapFile, PlayerId, ResignPolicy};
+use dataset::Recorder;
 
 pub struct ActiveGame {
     game: Game,
     connections: HashMap<PlayerId, ConnectionProxy>,
     ticks: Interval,
+    /// Records the game as training samples, if enabled
+    recorder: Option<Recorder>,
 }
 
 impl ActiveGame {
+    /// Start the given pending game, which must be ready. Return an error if the players cannot
+    /// be split into at least two teams, or if the map cannot be generated, in which case the
+    /// connections of the pending game are dropped.
+    pub fn new(pending_game: &mut PendingGame) -> Result<Self, GameError> {
+        let connections: HashMap<PlayerId, ConnectionProxy> =
+            FromIterator::from_iter(pending_game.connections.drain(..).enumerate());
+        let nb_teams = match pending_game.team_size {
+            Some(team_size) => {
+                let nb_players = connections.len();
+                let nb_teams = nb_players.checked_div(team_size as usize).unwrap_or(0);
+                if nb_teams < 2 {
+                    return Err(GameError::InvalidTeams {
+                        nb_players,
+                        team_size: team_size as usize,
+                    });
+                }
+                Some(nb_teams)
+            }
+            None => None,
+        };
+        let players: Vec<PlayerId> = connections.keys().cloned().collect();
+        let mut game = match pending_game.map_file {
+            Some(ref map_file) => match Game::with_map_file(players.clone(), map_file) {
+                Ok(game) => game,
+                Err(e) => {
+                    error!("cannot play on map {:?}: {}", map_file.name, e);
+                    Game::with_generator(players, &pending_game.generator)?
+                }
+            },
+            None => Game::with_generator(players, &pending_game.generator)?,
+        };
+        game.set_resign_policy(pending_game.resign_policy);
+        game.set_capture_rule(pending_game.capture_rule);
+        if let Some(nb_teams) = nb_teams {
+            // Alternate the teams, so that the players who joined first are not all teammates.
+            for player in connections.keys() {
+                game.set_team(*player, player % nb_teams);
+            }
+        }
+        Ok(ActiveGame {
+            connections,
+            game,
+            ticks: Interval::new(Instant::now(), Duration::from_millis(500)),
+            recorder: None,
+        })
+    }
+
+    /// Record the game, and export it as training samples in a new directory under `directory`
+    /// when it's over. See the `dataset` module.
+    pub fn record(&mut self, directory: PathBuf) {
+        self.recorder = Some(Recorder::new(directory, self.connections.len()));
+    }
+
     fn process_players_actions(&mut self) {
         trace!("processing players actions");
 
+        // Take a snapshot of what each player sees before any move is performed, since that's
+        // what the players' decisions are based on.
+        let observations: HashMap<PlayerId, _> = match self.recorder {
+            Some(ref recorder) => self
+                .connections
+                .iter()
+                .filter(|(player, _)| !self.game.is_defeated(**player))
+                .map(|(player, _)| (*player, recorder.observe(&self.game, *player)))
+                .collect(),
+            None => HashMap::new(),
+        };
+
         for (player, mut connection) in self
             .connections
             .iter_mut()
@@ -27,17 +98,33 @@ impl ActiveGame {
             .filter(|(_, c)| !c.has_resigned())
         {
             debug!("getting actions from player {}", player);
-            connection.poll_actions();
+            connection.poll_actions(&self.game, *player);
+            let mut performed = None;
             if connection.has_resigned() {
                 debug!("player {} resigned, notifying the game", player);
                 self.game.resign(*player);
-            } else if let Some(mut mv) = connection.get_move() {
+            } else if let Some(mv) = connection.get_move(&self.game, *player) {
                 debug!("player {} moved, notifying the game", player);
-                mv.player = *player;
-                self.game.perform_move(mv);
+                performed = self.game.perform_move(mv).map(|_| mv);
+                connection.pending_moves.set_status(mv, performed.is_some());
             } else {
                 debug!("no action for player {}", player);
             }
+            if let (Some(recorder), Some(observation)) =
+                (self.recorder.as_mut(), observations.get(player))
+            {
+                recorder.record(&self.game, *player, observation, performed);
+            }
+        }
+    }
+
+    /// Export the recorded samples, if the game is recorded.
+    fn export_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            match recorder.export(&self.game.winners()) {
+                Ok(directory) => info!("game recorded in {}", directory.display()),
+                Err(e) => error!("failed to export the game recording: {}", e),
+            }
         }
     }
 
@@ -53,7 +140,11 @@ impl ActiveGame {
         let update = game.get_update();
 
         for (player, mut connection) in connections.iter_mut().filter(|(_, c)| !c.has_resigned()) {
-            match connection.updates.start_send(update.filtered(*player)) {
+            let update = update
+                .filtered(*player)
+                .with_queue(&connection.pending_moves)
+                .with_ack(connection.last_seq);
+            match connection.updates.start_send(update) {
                 Ok(AsyncSink::Ready) => continue,
                 Ok(AsyncSink::NotReady(_)) => {
                     // If we can NotReady, the start_send attempt failed due to the sink being full.
@@ -114,6 +205,15 @@ impl Future for ActiveGame {
                     // To prevent the updates from being buffered, we call poll_complete on each
                     // sender
                     self.poll_complete_updates();
+                    if self.game.is_over() {
+                        info!(
+                            "game over at turn {}, winner: {:?}",
+                            self.game.turn(),
+                            self.game.winner()
+                        );
+                        self.export_recording();
+                        return Ok(Async::Ready(()));
+                    }
                 }
                 Async::Ready(None) => panic!("Unexpected end of ticks stream"),
                 Async::NotReady => return Ok(Async::NotReady),
@@ -122,9 +222,67 @@ impl Future for ActiveGame {
     }
 }
 
+/// An error that prevents a pending game from starting.
+#[derive(Debug)]
+pub enum GameError {
+    /// The players cannot be split into at least two teams of the given size.
+    InvalidTeams { nb_players: usize, team_size: usize },
+    /// The map could not be generated.
+    Generator(GeneratorError),
+}
+
+impl From<GeneratorError> for GameError {
+    fn from(err: GeneratorError) -> Self {
+        GameError::Generator(err)
+    }
+}
+
+impl Error for GameError {
+    fn description(&self) -> &str {
+        match *self {
+            GameError::InvalidTeams { .. } => "not enough players for two teams",
+            GameError::Generator(ref e) => e.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            GameError::InvalidTeams { .. } => None,
+            GameError::Generator(ref e) => Some(e),
+        }
+    }
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::InvalidTeams {
+                nb_players,
+                team_size,
+            } => write!(
+                f,
+                "Game error: {} players cannot be split into teams of {}",
+                nb_players, team_size
+            ),
+            GameError::Generator(e) => write!(f, "{}", e),
+        }
+    }
+}
+
 pub struct PendingGame {
     pub connections: Vec<ConnectionProxy>,
     pub size: u8,
+    /// Number of players in each team, if the game is played in teams. The size of the game
+    /// should then be a multiple of the team size.
+    pub team_size: Option<u8>,
+    /// What happens to the tiles of the players who resign
+    pub resign_policy: ResignPolicy,
+    /// What happens to the tiles of the players whose general is captured
+    pub capture_rule: CaptureRule,
+    /// Map to play on. If not set, or if it's not valid for the game, a random map is generated.
+    pub map_file: Option<MapFile>,
+    /// Settings of the random map, when the game is not played on a map file
+    pub generator: GeneratorConfig,
 }
 
 impl PendingGame {
@@ -132,6 +290,11 @@ impl PendingGame {
         PendingGame {
             connections: Vec::new(),
             size: 2,
+            team_size: None,
+            resign_policy: ResignPolicy::default(),
+            capture_rule: CaptureRule::default(),
+            map_file: None,
+            generator: GeneratorConfig::default(),
         }
     }
     pub fn add(&mut self, connection: ConnectionProxy) {
@@ -141,16 +304,3 @@ impl PendingGame {
         self.connections.len() == self.size as usize
     }
 }
-
-impl<'a> From<&'a mut PendingGame> for ActiveGame {
-    fn from(pending_game: &mut PendingGame) -> Self {
-        let connections: HashMap<PlayerId, ConnectionProxy> =
-            FromIterator::from_iter(pending_game.connections.drain(..).enumerate());
-        let game = Game::new(connections.keys().cloned().collect());
-        ActiveGame {
-            connections,
-            game,
-            ticks: Interval::new(Instant::now(), Duration::from_millis(500)),
-        }
-    }
-}
@@ -0,0 +1,35 @@
+use futures::sync::mpsc::channel;
+
+use connection::ConnectionProxy;
+use game::{ActiveGame, GameError, PendingGame};
+
+/// Return a pending game with the given number of players, played in teams of the given size.
+fn get_pending_game(nb_players: usize, team_size: u8) -> PendingGame {
+    let mut pending_game = PendingGame::new();
+    pending_game.size = nb_players as u8;
+    pending_game.team_size = Some(team_size);
+    for _ in 0..nb_players {
+        let (_, actions) = channel(10);
+        let (updates, _) = channel(10);
+        pending_game.add(ConnectionProxy::new(actions, updates));
+    }
+    pending_game
+}
+
+#[test]
+fn test_teams() {
+    assert!(ActiveGame::new(&mut get_pending_game(4, 2)).is_ok());
+    assert!(ActiveGame::new(&mut get_pending_game(2, 1)).is_ok());
+
+    // There must be at least two teams, so that each player has opponents.
+    for &(nb_players, team_size) in &[(2, 2), (3, 2), (2, 3), (2, 0)] {
+        match ActiveGame::new(&mut get_pending_game(nb_players, team_size)) {
+            Err(GameError::InvalidTeams { .. }) => {}
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!(
+                "{} players were split into teams of {}",
+                nb_players, team_size
+            ),
+        }
+    }
+}
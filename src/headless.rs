@@ -0,0 +1,206 @@
+//! This module contains a game runner for bots only. Contrary to `ActiveGame`, it does not rely
+//! on a timer: a new turn starts as soon as all the bots answered the previous update.
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use bot::{self, Bot, BotError};
+use connection::{queue_action, DEFAULT_MAX_QUEUE_LEN, DEFAULT_STALE_WINDOW};
+use core::{Action, Game, GeneratorError, MoveQueue, PlayerId, StampedAction};
+
+/// Default maximum number of turns of a headless game.
+pub const DEFAULT_MAX_TURNS: usize = 2000;
+
+/// A bot taking part in a headless game.
+struct Seat {
+    bot: Box<dyn Bot>,
+    pending_moves: MoveQueue,
+    /// Sequence number of the last action of the bot
+    last_seq: Option<u64>,
+    resigned: bool,
+}
+
+/// The result of a headless game.
+#[derive(Debug)]
+pub struct Outcome {
+    /// The winner of the game, if any. There is no winner if the game reached the maximum number
+    /// of turns.
+    pub winner: Option<PlayerId>,
+    /// Number of turns played.
+    pub turns: usize,
+}
+
+pub struct HeadlessGame {
+    game: Game,
+    seats: HashMap<PlayerId, Seat>,
+    max_turns: usize,
+}
+
+impl HeadlessGame {
+    /// Start the given bots (see `bot::from_spec`), and create a new game for them. Each bot
+    /// gets the time `budget` to answer each update.
+    pub fn new(
+        specs: &[String],
+        budget: Duration,
+        max_turns: usize,
+    ) -> Result<Self, HeadlessError> {
+        let game = Game::new((0..specs.len()).collect())?;
+        let bots = start_bots(specs, budget)?;
+        Ok(Self::from_bots(bots, game, max_turns))
+    }
+
+    /// Like `HeadlessGame::new`, but the map is generated from the given seed: the same bots
+    /// with the same seed always play on the same map, from the same positions. The bot at index
+    /// `i` plays as player `i`.
+    pub fn with_seed(
+        specs: &[String],
+        budget: Duration,
+        max_turns: usize,
+        seed: u64,
+    ) -> Result<Self, HeadlessError> {
+        let game = Game::with_seed((0..specs.len()).collect(), seed)?;
+        let bots = start_bots(specs, budget)?;
+        Ok(Self::from_bots(bots, game, max_turns))
+    }
+
+    fn from_bots(bots: Vec<Box<dyn Bot>>, game: Game, max_turns: usize) -> Self {
+        let seats = bots
+            .into_iter()
+            .enumerate()
+            .map(|(player, bot)| {
+                let seat = Seat {
+                    bot,
+                    pending_moves: MoveQueue::new(),
+                    last_seq: None,
+                    resigned: false,
+                };
+                (player, seat)
+            })
+            .collect();
+        HeadlessGame {
+            game,
+            seats,
+            max_turns,
+        }
+    }
+
+    /// Play the game until it's over or until the maximum number of turns is reached.
+    pub fn run(mut self) -> Outcome {
+        loop {
+            let update = self.game.get_update();
+            if self.game.is_over() || self.game.turn() >= self.max_turns {
+                break;
+            }
+            let HeadlessGame {
+                ref mut game,
+                ref mut seats,
+                ..
+            } = self;
+
+            for (player, seat) in seats.iter_mut().filter(|(_, s)| !s.resigned) {
+                let update = update
+                    .filtered(*player)
+                    .with_queue(&seat.pending_moves)
+                    .with_ack(seat.last_seq);
+                match seat.bot.play(&update) {
+                    Ok(None) => {}
+                    // The bot is killed when it fails, so we treat errors as resignations.
+                    Ok(Some(StampedAction {
+                        action: Action::Resign,
+                        ..
+                    }))
+                    | Err(_) => {
+                        debug!("player {} resigned, notifying the game", player);
+                        seat.resigned = true;
+                        seat.pending_moves.clear();
+                        game.resign(*player);
+                    }
+                    Ok(Some(stamped)) => {
+                        if stamped.seq.is_some() {
+                            seat.last_seq = stamped.seq;
+                        }
+                        queue_action(
+                            &mut seat.pending_moves,
+                            game,
+                            *player,
+                            &stamped,
+                            DEFAULT_STALE_WINDOW,
+                            DEFAULT_MAX_QUEUE_LEN,
+                        );
+                    }
+                }
+            }
+
+            for (player, seat) in seats.iter_mut().filter(|(_, s)| !s.resigned) {
+                let mv = seat.pending_moves.pop(game, *player);
+                seat.pending_moves.truncate(DEFAULT_MAX_QUEUE_LEN);
+                if let Some(mv) = mv {
+                    let performed = game.perform_move(mv).is_some();
+                    seat.pending_moves.set_status(mv, performed);
+                }
+            }
+            game.incr_turn();
+        }
+
+        let outcome = Outcome {
+            winner: self.game.winner(),
+            turns: self.game.turn(),
+        };
+        info!("headless game finished: {:?}", outcome);
+        outcome
+    }
+}
+
+fn start_bots(specs: &[String], budget: Duration) -> Result<Vec<Box<dyn Bot>>, BotError> {
+    specs
+        .iter()
+        .map(|spec| bot::from_spec(spec, budget))
+        .collect()
+}
+
+/// An error that prevents a headless game from starting.
+#[derive(Debug)]
+pub enum HeadlessError {
+    /// A bot could not be started.
+    Bot(BotError),
+    /// The map could not be generated.
+    Generator(GeneratorError),
+}
+
+impl From<BotError> for HeadlessError {
+    fn from(err: BotError) -> Self {
+        HeadlessError::Bot(err)
+    }
+}
+
+impl From<GeneratorError> for HeadlessError {
+    fn from(err: GeneratorError) -> Self {
+        HeadlessError::Generator(err)
+    }
+}
+
+impl Error for HeadlessError {
+    fn description(&self) -> &str {
+        match *self {
+            HeadlessError::Bot(ref e) => e.description(),
+            HeadlessError::Generator(ref e) => e.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            HeadlessError::Bot(ref e) => Some(e),
+            HeadlessError::Generator(ref e) => Some(e),
+        }
+    }
+}
+
+impl fmt::Display for HeadlessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeadlessError::Bot(e) => write!(f, "{}", e),
+            HeadlessError::Generator(e) => write!(f, "{}", e),
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use external_tests::script_bot;
+use headless::HeadlessGame;
+
+const BUDGET: Duration = Duration::from_millis(500);
+
+#[test]
+fn test_max_turns() {
+    let specs = vec!["mcts:5".to_string(), "mcts:5".to_string()];
+    let outcome = HeadlessGame::with_seed(&specs, BUDGET, 10, 0)
+        .unwrap()
+        .run();
+    assert_eq!(outcome.turns, 10);
+    assert_eq!(outcome.winner, None);
+}
+
+#[test]
+fn test_resign() {
+    let resign = script_bot(
+        "resign",
+        r#"read update; echo '{"type":"resign"}'; read update"#,
+    );
+    let specs = vec![resign, "mcts:5".to_string()];
+    let outcome = HeadlessGame::with_seed(&specs, BUDGET, 10, 0)
+        .unwrap()
+        .run();
+    assert_eq!(outcome.winner, Some(1));
+    assert!(outcome.turns < 10);
+}
@@ -5,6 +5,7 @@ extern crate rand;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
 extern crate serde_json;
 #[macro_use]
 extern crate log;
@@ -15,26 +16,358 @@ extern crate tokio_timer;
 extern crate tokio_tungstenite;
 extern crate tungstenite;
 
+mod bot;
 mod connection;
 mod core;
+mod dataset;
+mod env;
+mod external;
 mod game;
+mod headless;
+mod mcts;
 mod server;
+mod tournament;
+mod vec_env;
+
+#[cfg(test)]
+mod bot_tests;
+#[cfg(test)]
+mod connection_tests;
+#[cfg(test)]
+mod dataset_tests;
+#[cfg(test)]
+mod env_tests;
+#[cfg(test)]
+mod external_tests;
+#[cfg(test)]
+mod game_tests;
+#[cfg(test)]
+mod headless_tests;
+#[cfg(test)]
+mod mcts_tests;
+#[cfg(test)]
+mod tournament_tests;
+#[cfg(test)]
+mod vec_env_tests;
 
-use std::env;
+use std::io;
+use std::path::PathBuf;
+use std::process;
 use std::thread;
+use std::time::Duration;
 
-use server::Server;
+use core::{
+    CaptureRule, Fairness, Game, GeneratorConfig, MapFile, MapPool, ResignPolicy, Rotation, Shape,
+    Strategy, Symmetry, Topology,
+};
+use env::Env;
+use headless::HeadlessGame;
+use server::{Config, Server};
+use tournament::{Format, Tournament};
+use vec_env::VecEnv;
 
 use actix_web::{fs::StaticFiles, middleware, server as actix_server, App};
 
+const USAGE: &str = "\
+usage:
+    generals-rs [ADDR] [--bot BOT]... [--bot-budget MS] [--record DIR] [--max-queue N]
+                [--stale-window N] [--team-size N] [--on-resign neutral|abandoned]
+                [--capture-rule full|halve] [--map FILE | --map-dir DIR]
+                [--rotation random|round-robin|weighted]
+                [--generator STRATEGY] [--shape rectangle|island|ring|cross]
+                [--topology flat|torus] [--symmetry none|point|mirror|rotational]
+                [--fairness SPEC]
+    generals-rs headless [--bot-budget MS] [--max-turns N] BOT...
+    generals-rs env [--players N] [--max-turns N] [--envs N [--threads N]]
+    generals-rs tournament [--format round-robin|swiss] [--rounds N] [--seeds N]
+                           [--seed N] [--threads N] [--bot-budget MS] [--max-turns N]
+                           BOT...
+
+BOT is either `mcts` (built-in bot searching for the whole budget), `mcts:N`
+(built-in bot running N iterations per turn), or the command of an external bot.
+
+STRATEGY is how random maps are generated: `random[:DENSITY]`,
+`cave[:DENSITY]`, `maze[:WIDTH]` or `lanes[:LANES[:WIDTH]]`.
+
+SPEC gives the maximum spread of the metrics of random maps, as comma separated
+`metric=N` pairs: `distance`, `open`, `city` and `choke`, measured within
+`radius` moves of the generals. For instance `radius=6,distance=2,open=4`.";
+
+/// Command line options
+struct Options {
+    /// Positional arguments
+    args: Vec<String>,
+    /// Specifications of the bots (see `bot::from_spec`)
+    bots: Vec<String>,
+    /// Time bots have to answer each update
+    bot_budget: Duration,
+    /// Directory in which games are recorded
+    record: Option<PathBuf>,
+    /// Maximum number of moves each player can queue
+    max_queue_len: usize,
+    /// Number of turns after which moves sent by the players are discarded
+    stale_window: usize,
+    /// Number of players in each team of the server's games
+    team_size: Option<usize>,
+    /// What happens to the tiles of the players who resign in the server's games
+    resign_policy: ResignPolicy,
+    /// What happens to the tiles of the players whose general is captured in the server's games
+    capture_rule: CaptureRule,
+    /// Map file the server's games are played on
+    map: Option<PathBuf>,
+    /// Directory of the maps the server's games are played on
+    map_dir: Option<PathBuf>,
+    /// How the maps of the directory are picked
+    rotation: Rotation,
+    /// Settings of the random maps of the server's games
+    generator: GeneratorConfig,
+    /// Maximum number of turns for headless games and environment episodes
+    max_turns: usize,
+    /// Number of players for environment episodes
+    players: usize,
+    /// Number of environments to run in parallel. When it's more than one, a `VecEnv` is served.
+    envs: usize,
+    /// Number of threads used to run the environments, or the tournament games
+    threads: usize,
+    /// Pairing format of tournaments
+    format: String,
+    /// Number of rounds of Swiss tournaments
+    rounds: Option<usize>,
+    /// Number of seeds each tournament match is played on
+    seeds: usize,
+    /// First seed of tournaments, or of environment episodes
+    seed: Option<u64>,
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1);
+}
+
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Options {
+    let mut options = Options {
+        args: Vec::new(),
+        bots: Vec::new(),
+        bot_budget: external::DEFAULT_BUDGET,
+        record: None,
+        max_queue_len: connection::DEFAULT_MAX_QUEUE_LEN,
+        stale_window: connection::DEFAULT_STALE_WINDOW,
+        team_size: None,
+        resign_policy: ResignPolicy::default(),
+        capture_rule: CaptureRule::default(),
+        map: None,
+        map_dir: None,
+        rotation: Rotation::Random,
+        generator: GeneratorConfig::default(),
+        max_turns: headless::DEFAULT_MAX_TURNS,
+        players: 2,
+        envs: 1,
+        threads: 1,
+        format: "round-robin".to_string(),
+        rounds: None,
+        seeds: 10,
+        seed: None,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--bot" => options.bots.push(value()),
+            "--record" => options.record = Some(PathBuf::from(value())),
+            "--bot-budget" => {
+                let ms = value().parse().unwrap_or_else(|_| usage());
+                options.bot_budget = Duration::from_millis(ms);
+            }
+            "--max-queue" => options.max_queue_len = value().parse().unwrap_or_else(|_| usage()),
+            "--stale-window" => options.stale_window = value().parse().unwrap_or_else(|_| usage()),
+            "--team-size" => match value().parse() {
+                Ok(team_size) if team_size > 0 => options.team_size = Some(team_size),
+                _ => usage(),
+            },
+            "--on-resign" => {
+                options.resign_policy = ResignPolicy::from_name(&value()).unwrap_or_else(|| usage())
+            }
+            "--capture-rule" => {
+                options.capture_rule = CaptureRule::from_name(&value()).unwrap_or_else(|| usage())
+            }
+            "--map" => options.map = Some(PathBuf::from(value())),
+            "--map-dir" => options.map_dir = Some(PathBuf::from(value())),
+            "--rotation" => {
+                options.rotation = Rotation::from_name(&value()).unwrap_or_else(|| usage())
+            }
+            "--generator" => {
+                options.generator.strategy =
+                    Strategy::from_spec(&value()).unwrap_or_else(|| usage())
+            }
+            "--shape" => {
+                options.generator.shape = Shape::from_name(&value()).unwrap_or_else(|| usage())
+            }
+            "--topology" => {
+                options.generator.topology =
+                    Topology::from_name(&value()).unwrap_or_else(|| usage())
+            }
+            "--symmetry" => {
+                options.generator.symmetry =
+                    Symmetry::from_name(&value()).unwrap_or_else(|| usage())
+            }
+            "--fairness" => {
+                options.generator.fairness =
+                    Fairness::from_spec(&value()).unwrap_or_else(|| usage())
+            }
+            "--max-turns" => options.max_turns = value().parse().unwrap_or_else(|_| usage()),
+            "--players" => options.players = value().parse().unwrap_or_else(|_| usage()),
+            "--envs" => options.envs = value().parse().unwrap_or_else(|_| usage()),
+            "--threads" => options.threads = value().parse().unwrap_or_else(|_| usage()),
+            "--format" => options.format = value(),
+            "--rounds" => options.rounds = Some(value().parse().unwrap_or_else(|_| usage())),
+            "--seeds" => options.seeds = value().parse().unwrap_or_else(|_| usage()),
+            "--seed" => options.seed = Some(value().parse().unwrap_or_else(|_| usage())),
+            "-h" | "--help" => usage(),
+            _ => options.args.push(arg),
+        }
+    }
+    options
+}
+
+/// Play a game between bots only, without starting the server.
+fn run_headless(options: Options) {
+    if options.args.len() < 2 {
+        usage();
+    }
+    let game = HeadlessGame::new(&options.args, options.bot_budget, options.max_turns)
+        .unwrap_or_else(|e| {
+            eprintln!("failed to start the game: {}", e);
+            process::exit(1);
+        });
+    let outcome = game.run();
+    match outcome.winner {
+        Some(winner) => println!("player {} won after {} turns", winner, outcome.turns),
+        None => println!("no winner after {} turns", outcome.turns),
+    }
+}
+
+/// Play a tournament between bots, and print the standings.
+fn run_tournament(options: Options) {
+    if options.args.len() < 2 || options.seeds == 0 {
+        usage();
+    }
+    let format = match Format::from_name(&options.format, options.args.len()) {
+        Some(Format::Swiss { rounds }) => Format::Swiss {
+            rounds: options.rounds.unwrap_or(rounds),
+        },
+        Some(format) => format,
+        None => usage(),
+    };
+    let config = tournament::Config {
+        format,
+        seeds: options.seeds,
+        seed: options.seed.unwrap_or_else(|| rand::random::<u32>() as u64),
+        budget: options.bot_budget,
+        max_turns: options.max_turns,
+        threads: options.threads,
+    };
+    println!(
+        "seeds: {}..{}",
+        config.seed,
+        config.seed.wrapping_add(config.seeds as u64)
+    );
+    let mut tournament = Tournament::new(options.args, config);
+    if let Err(e) = tournament.run() {
+        eprintln!("tournament aborted: {}", e);
+        process::exit(1);
+    }
+    print!("{}", tournament);
+}
+
+/// Serve a reinforcement learning environment over stdin/stdout.
+fn run_env(options: Options) {
+    if options.players < 2 {
+        usage();
+    }
+    // The environments generate a new map for each episode, make sure it's possible
+    if let Err(e) = Game::new((0..options.players).collect()) {
+        eprintln!("cannot create the environment: {}", e);
+        process::exit(1);
+    }
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let result = if options.envs > 1 {
+        VecEnv::new(
+            options.envs,
+            options.players,
+            options.threads,
+            options.max_turns,
+            Default::default(),
+        )
+        .serve(stdin.lock(), stdout.lock())
+    } else {
+        Env::new(options.players, options.max_turns, Default::default())
+            .serve(stdin.lock(), stdout.lock())
+    };
+    if let Err(e) = result {
+        eprintln!("environment terminated with an error: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Load the maps the server's games are played on, either from a directory or from a single map
+/// file. Exit if they cannot be loaded.
+fn load_map_pool(options: &Options) -> Option<MapPool> {
+    let (path, pool) = if let Some(ref directory) = options.map_dir {
+        (directory, MapPool::load(directory, options.rotation))
+    } else if let Some(ref path) = options.map {
+        let pool = MapFile::load(path)
+            .and_then(|map_file| map_file.build().map(|_| map_file))
+            .map(|map_file| MapPool::new(vec![map_file], options.rotation));
+        (path, pool)
+    } else {
+        return None;
+    };
+    match pool {
+        Ok(pool) => {
+            if pool.is_empty() {
+                eprintln!("no valid map in {}: playing on random maps", path.display());
+            }
+            Some(pool)
+        }
+        Err(e) => {
+            eprintln!("failed to load {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
 fn main() {
     env_logger::init();
-    let addr = env::args()
-        .nth(1)
-        .unwrap_or_else(|| "127.0.0.1:8080".to_string())
+    let mut args = std::env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("headless") => return run_headless(parse_options(args.skip(1))),
+        Some("env") => return run_env(parse_options(args.skip(1))),
+        Some("tournament") => return run_tournament(parse_options(args.skip(1))),
+        _ => {}
+    }
+
+    let options = parse_options(args);
+    let addr = options
+        .args
+        .first()
+        .map(String::as_str)
+        .unwrap_or("127.0.0.1:8080")
         .parse()
         .unwrap();
-    thread::spawn(move || Server::run(&addr));
+    let map_pool = load_map_pool(&options);
+    let config = Config {
+        bots: options.bots,
+        bot_budget: options.bot_budget,
+        record: options.record,
+        max_queue_len: options.max_queue_len,
+        stale_window: options.stale_window,
+        team_size: options.team_size,
+        resign_policy: options.resign_policy,
+        capture_rule: options.capture_rule,
+        map_pool,
+        generator: options.generator,
+    };
+    thread::spawn(move || Server::run(&addr, config));
     actix_server::new(|| {
         App::new()
             .middleware(middleware::Logger::default())
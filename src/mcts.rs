@@ -0,0 +1,362 @@
+//! This module contains a built-in bot that plays with Monte Carlo tree search (MCTS).
+//!
+//! The bot only knows what its player is allowed to know: it remembers what it saw of the map in
+//! the updates it received. Before each iteration of the search, it samples a complete map that
+//! is consistent with what it knows (this is called determinization): the tiles it never saw are
+//! what the fog of war shows, the tiles it saw but cannot see anymore are as it last saw them, and
+//! the generals it never saw are placed on random tiles it never saw. Simulations are played with
+//! the engine's forward model (see `core::State`), so they follow exactly the rules of the game.
+//!
+//! The search tree only contains the bot's own moves, one per turn. The moves of the other
+//! players, as well as the bot's moves beyond the tree, are chosen by a simple greedy policy. The
+//! position reached after `HORIZON` turns is evaluated with the share of land and army owned by
+//! the bot.
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+use rand::{rngs::StdRng, FromEntropy, Rng};
+
+use bot::{Bot, BotError};
+use core::{
+    Action, Move, MoveOutcome, Player, PlayerId, StampedAction, State, Teams, TileKind, TileState,
+    Topology, Update,
+};
+
+/// Number of turns simulated by each iteration of the search
+const HORIZON: usize = 20;
+
+/// Exploration constant of the UCT formula
+const EXPLORATION: f64 = 0.2;
+
+/// How much the bot can think about each move.
+#[derive(Copy, Clone, Debug)]
+pub enum Budget {
+    /// Search for the given time
+    Time(Duration),
+    /// Run the given number of iterations
+    Iterations(usize),
+}
+
+/// What the bot knows about the game.
+#[derive(Default)]
+pub(crate) struct Memory {
+    width: usize,
+    height: usize,
+    topology: Topology,
+    turn: usize,
+    /// The bot's player, which is the player the updates are filtered for
+    pub(crate) me: Option<PlayerId>,
+    players: Vec<Player>,
+    /// The tiles, as last reported in the updates. Tiles in the fog of war are reported as they
+    /// look in the fog.
+    reported: Vec<TileState>,
+    /// The tiles, as the bot last saw them
+    known: Vec<TileState>,
+    /// Whether the bot saw each tile at least once
+    seen: Vec<bool>,
+}
+
+impl Memory {
+    pub(crate) fn update(&mut self, update: &Update) {
+        let nb_tiles = update.width() * update.height();
+        if self.reported.len() != nb_tiles {
+            let mountain = TileState {
+                kind: TileKind::Mountain,
+                owner: None,
+                units: 0,
+            };
+            self.width = update.width();
+            self.height = update.height();
+            self.reported = vec![mountain; nb_tiles];
+            self.known = vec![mountain; nb_tiles];
+            self.seen = vec![false; nb_tiles];
+        }
+        self.topology = update.topology();
+        self.turn = update.turn();
+        self.me = update.player();
+        self.players = update.players().cloned().collect();
+        for (index, tile) in update.tiles() {
+            self.reported[*index] = tile.state();
+        }
+
+        let visible = self.visible_tiles();
+        for (index, visible) in visible.into_iter().enumerate() {
+            if visible {
+                self.known[index] = self.reported[index];
+                self.seen[index] = true;
+            } else if !self.seen[index] {
+                // The fog of war only tells whether the tile is an obstacle.
+                self.known[index] = TileState {
+                    owner: None,
+                    units: 0,
+                    ..self.reported[index]
+                };
+            }
+        }
+    }
+
+    /// Return whether each tile is currently visible: a player can see the tiles that surround
+    /// the tiles it or its allies own, including diagonally, and across the edges of toroidal
+    /// maps.
+    pub(crate) fn visible_tiles(&self) -> Vec<bool> {
+        let mut visible = vec![false; self.reported.len()];
+        let me = match self.me {
+            Some(me) => me,
+            None => return visible,
+        };
+        let teams = Teams::new(&self.players);
+        let owned = self
+            .reported
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| tile.owner.is_some_and(|owner| teams.are_allies(me, owner)));
+        for (index, _) in owned {
+            let (column, line) = ((index % self.width) as isize, (index / self.width) as isize);
+            let (width, height) = (self.width as isize, self.height as isize);
+            for l in (line - 1)..=(line + 1) {
+                for c in (column - 1)..=(column + 1) {
+                    if self.topology == Topology::Torus {
+                        let (c, l) = (c.rem_euclid(width), l.rem_euclid(height));
+                        visible[(l * width + c) as usize] = true;
+                    } else if l >= 0 && c >= 0 && l < height && c < width {
+                        visible[(l * width + c) as usize] = true;
+                    }
+                }
+            }
+        }
+        visible
+    }
+
+    /// Return a complete state of the game that is consistent with what the bot knows.
+    fn determinize<R: Rng>(&self, rng: &mut R) -> State {
+        let mut tiles = self.known.clone();
+        let mut candidates: Vec<usize> = (0..tiles.len())
+            .filter(|i| !self.seen[*i] && tiles[*i].kind == TileKind::Open)
+            .collect();
+        for player in self.players.iter().filter(|p| !p.defeated()) {
+            let known_general = tiles
+                .iter()
+                .any(|t| t.kind == TileKind::General && t.owner == Some(player.id));
+            if known_general || candidates.is_empty() {
+                continue;
+            }
+            let index = candidates.swap_remove(rng.gen_range(0, candidates.len()));
+            // A general gets a unit every other turn, but some of them have been moved away.
+            tiles[index] = TileState {
+                kind: TileKind::General,
+                owner: Some(player.id),
+                units: (self.turn / 4 + 1) as u16,
+            };
+        }
+        State::from_tiles(
+            self.width,
+            self.height,
+            self.topology,
+            &tiles,
+            self.turn,
+            &self.players,
+        )
+    }
+}
+
+/// A node of the search tree. Since the opponents' moves are sampled, the same node can
+/// correspond to different states: it represents a sequence of the bot's moves.
+#[derive(Default)]
+struct Node {
+    visits: u32,
+    /// Sum of the values of all the simulations that went through this node
+    value: f64,
+    /// The bot's possible moves, with the nodes they lead to. `None` means doing nothing.
+    children: Vec<(Option<Move>, Node)>,
+}
+
+impl Node {
+    /// Return the index of the child to explore, using the UCT formula. Unvisited children are
+    /// explored first.
+    fn select(&self) -> usize {
+        let log_visits = f64::from(self.visits).ln();
+        let mut best = (0, f64::MIN);
+        for (i, (_, child)) in self.children.iter().enumerate() {
+            if child.visits == 0 {
+                return i;
+            }
+            let visits = f64::from(child.visits);
+            let score = child.value / visits + EXPLORATION * (log_visits / visits).sqrt();
+            if score > best.1 {
+                best = (i, score);
+            }
+        }
+        best.0
+    }
+}
+
+pub struct MctsBot {
+    budget: Budget,
+    memory: Memory,
+    rng: StdRng,
+}
+
+impl MctsBot {
+    pub fn new(budget: Budget) -> Self {
+        MctsBot {
+            budget,
+            memory: Memory::default(),
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Search for the best move, given what the bot currently knows.
+    fn search(&mut self) -> Option<Move> {
+        let me = self.memory.me?;
+        let start = Instant::now();
+        let mut root = Node::default();
+        let mut iterations = 0;
+        loop {
+            let mut state = self.memory.determinize(&mut self.rng);
+            let _ = self.iterate(&mut root, &mut state, me, HORIZON);
+            iterations += 1;
+            let done = match self.budget {
+                Budget::Time(budget) => start.elapsed() >= budget,
+                Budget::Iterations(budget) => iterations >= budget,
+            };
+            if done {
+                break;
+            }
+        }
+        debug!(
+            "mcts: {} iterations in {:?}, value {:.3}",
+            iterations,
+            start.elapsed(),
+            root.value / f64::from(root.visits)
+        );
+        root.children
+            .iter()
+            .max_by_key(|(_, child)| child.visits)
+            .and_then(|(mv, _)| *mv)
+    }
+
+    /// Run one iteration of the search from the given node: select a path in the tree, expand
+    /// it, and simulate the rest of the game up to `depth` turns. Return the value of the
+    /// simulation.
+    fn iterate(&mut self, node: &mut Node, state: &mut State, me: PlayerId, depth: usize) -> f64 {
+        let value = if depth == 0 || state.is_over() {
+            evaluate(state, me)
+        } else if node.visits == 0 {
+            node.children = state
+                .legal_moves(me)
+                .into_iter()
+                .map(|mv| (Some(mv), Node::default()))
+                .collect();
+            if node.children.is_empty() {
+                node.children.push((None, Node::default()));
+            }
+            node.children.shuffle(&mut self.rng);
+            self.rollout(state, me, depth)
+        } else {
+            let i = node.select();
+            let mv = node.children[i].0;
+            self.play_turn(state, me, mv);
+            self.iterate(&mut node.children[i].1, state, me, depth - 1)
+        };
+        node.visits += 1;
+        node.value += value;
+        value
+    }
+
+    /// Play the given number of turns, with all the players following the greedy policy, and
+    /// return the value of the resulting state.
+    fn rollout(&mut self, state: &mut State, me: PlayerId, depth: usize) -> f64 {
+        for _ in 0..depth {
+            if state.is_over() {
+                break;
+            }
+            let mv = greedy_move(state, me, &mut self.rng);
+            self.play_turn(state, me, mv);
+        }
+        evaluate(state, me)
+    }
+
+    /// Play a turn where the bot makes the given move, and the other players follow the greedy
+    /// policy.
+    fn play_turn(&mut self, state: &mut State, me: PlayerId, mv: Option<Move>) {
+        let players: Vec<PlayerId> = state.players().collect();
+        let mut moves = Vec::with_capacity(players.len());
+        for player in players {
+            let mv = if player == me {
+                mv
+            } else if state.is_defeated(player) {
+                None
+            } else {
+                greedy_move(state, player, &mut self.rng)
+            };
+            moves.extend(mv);
+        }
+        let _ = state.apply(&moves);
+    }
+}
+
+impl Bot for MctsBot {
+    fn play(&mut self, update: &Update) -> Result<Option<StampedAction>, BotError> {
+        self.memory.update(update);
+        // The move is based on the update, so it's stale if the search takes too long.
+        Ok(self.search().map(|mv| StampedAction {
+            action: Action::Move(mv),
+            turn: Some(update.turn()),
+            seq: None,
+        }))
+    }
+}
+
+/// Return a move for the given player: one of the moves that capture the most valuable tile,
+/// or if none of them captures anything, a move from the tile with the most units to another tile
+/// of the player, to gather the army. Failed attacks are only made as a last resort.
+fn greedy_move<R: Rng>(state: &State, player: PlayerId, rng: &mut R) -> Option<Move> {
+    let moves = state.legal_moves_with_outcome(player);
+    let reinforces = |mv: &Move| {
+        let to = state.grid().neighbor(mv.from, mv.direction);
+        to.map(|to| state.tile(to).owner) == Some(Some(player))
+    };
+    let priority = |(mv, outcome): &(Move, MoveOutcome)| match outcome {
+        MoveOutcome::GeneralCaptured(_) => (4, 0),
+        MoveOutcome::TileCaptured(Some(_)) => (3, 0),
+        MoveOutcome::TileCaptured(None) => (2, 0),
+        MoveOutcome::StatuQuo if reinforces(mv) => (1, state.tile(mv.from).units),
+        MoveOutcome::StatuQuo => (0, 0),
+    };
+    let best = moves.iter().map(priority).max()?;
+    let best_moves: Vec<Move> = moves
+        .iter()
+        .filter(|m| priority(m) == best)
+        .map(|(mv, _)| *mv)
+        .collect();
+    Some(best_moves[rng.gen_range(0, best_moves.len())])
+}
+
+/// Return the value of the given state for the given player, between `0` (defeat) and `1`
+/// (victory of the player's team). If the game is not over, this is the share of land and army
+/// the player owns.
+fn evaluate(state: &State, me: PlayerId) -> f64 {
+    if state.is_defeated(me) {
+        return 0.0;
+    }
+    if state
+        .winner()
+        .is_some_and(|winner| state.are_allies(winner, me))
+    {
+        return 1.0;
+    }
+    let score = |player| {
+        let (land, army) = state.land_and_army(player);
+        (5 * land + army) as f64
+    };
+    let total: f64 = state
+        .players()
+        .filter(|p| !state.is_defeated(*p))
+        .map(score)
+        .sum();
+    if total == 0.0 {
+        0.0
+    } else {
+        score(me) / total
+    }
+}
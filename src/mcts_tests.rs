@@ -0,0 +1,72 @@
+use bot::Bot;
+use core::{Action, Game, PlayerId, StampedAction, Update};
+use mcts::{Budget, MctsBot, Memory};
+
+/// Return a game where players 0 and 1 are teammates against players 2 and 3.
+fn team_game() -> Game {
+    let mut game = Game::with_seed(vec![0, 1, 2, 3], 42).unwrap();
+    game.set_team(0, 0);
+    game.set_team(1, 0);
+    game.set_team(2, 1);
+    game.set_team(3, 1);
+    game
+}
+
+fn general_of(update: &Update, player: PlayerId) -> usize {
+    update
+        .tiles()
+        .iter()
+        .find(|(_, tile)| tile.is_general() && tile.owner() == Some(player))
+        .map(|(index, _)| *index)
+        .unwrap()
+}
+
+#[test]
+fn test_memory_team() {
+    let update = team_game().get_update().filtered(1);
+    let mut memory = Memory::default();
+    memory.update(&update);
+    // The teammate's general is in the update, but the bot plays for the player the update is
+    // filtered for.
+    assert_eq!(memory.me, Some(1));
+
+    // The tiles around the teammate's general are visible.
+    let visible = memory.visible_tiles();
+    assert!(visible[general_of(&update, 0)]);
+    assert!(visible[general_of(&update, 1)]);
+    for (index, tile) in update
+        .tiles()
+        .iter()
+        .filter(|(_, tile)| !tile.is_mountain())
+    {
+        assert_eq!(
+            visible[*index],
+            tile.is_visible_by(0) || tile.is_visible_by(1)
+        );
+    }
+}
+
+#[test]
+fn test_bot_team() {
+    let mut game = team_game();
+    let update = game.get_update().filtered(1);
+    let general = general_of(&update, 1);
+    let mut bot = MctsBot::new(Budget::Iterations(20));
+    assert!(bot.play(&update).unwrap().is_none());
+    for _ in 0..4 {
+        game.incr_turn();
+    }
+    // The general is the only tile of the player that has units to move.
+    match bot.play(&game.get_update().filtered(1)).unwrap() {
+        Some(StampedAction {
+            action: Action::Move(mv),
+            turn,
+            ..
+        }) => {
+            assert_eq!(mv.player, 1);
+            assert_eq!(mv.from, general);
+            assert_eq!(turn, Some(4));
+        }
+        action => panic!("unexpected action {:?}", action),
+    }
+}
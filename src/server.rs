@@ -1,6 +1,9 @@
+use std::cmp;
 use std::io;
 use std::mem;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use futures::stream::Stream;
 use futures::sync::mpsc::{channel, Receiver, Sender};
@@ -9,25 +12,55 @@ use tokio_core::net::TcpListener;
 use tokio_core::reactor::{Core, Handle};
 use tokio_tungstenite::accept_async;
 
+use bot;
 use connection::{Connection, ConnectionProxy};
+use core::{CaptureRule, GeneratorConfig, MapPool, ResignPolicy};
 use game::{ActiveGame, PendingGame};
 
+/// Server settings
+pub struct Config {
+    /// Bots to add to each game (see `bot::from_spec`)
+    pub bots: Vec<String>,
+    /// Time bots have to answer each update
+    pub bot_budget: Duration,
+    /// If set, games are recorded as training samples in this directory
+    pub record: Option<PathBuf>,
+    /// Maximum number of moves each player can queue
+    pub max_queue_len: usize,
+    /// Number of turns after which the moves of the players are considered stale
+    pub stale_window: usize,
+    /// Number of players in each team. If not set, games are free for all.
+    pub team_size: Option<usize>,
+    /// What happens to the tiles of the players who resign
+    pub resign_policy: ResignPolicy,
+    /// What happens to the tiles of the players whose general is captured
+    pub capture_rule: CaptureRule,
+    /// Maps to play on. Random maps are generated when none of them is made for the number of
+    /// players of a game.
+    pub map_pool: Option<MapPool>,
+    /// Settings of the random maps
+    pub generator: GeneratorConfig,
+}
+
 pub struct Server {
     pending_game: PendingGame,
     connections: Receiver<ConnectionProxy>,
     handle: Handle,
+    config: Config,
 }
 
 impl Server {
-    pub fn run(addr: &SocketAddr) {
+    pub fn run(addr: &SocketAddr, config: Config) {
         let mut core = Core::new().unwrap();
         let handle = core.handle();
         let (connection_tx, connection_rx) = channel(100);
-        let server = Server {
+        let mut server = Server {
             pending_game: PendingGame::new(),
             connections: connection_rx,
             handle: handle.clone(),
+            config,
         };
+        server.pending_game = server.new_pending_game();
         handle.spawn(server.map_err(|e| error!("{}", e)));
 
         let socket = TcpListener::bind(&addr, &handle).unwrap();
@@ -68,6 +101,46 @@ impl Server {
             })
             .unwrap();
     }
+
+    /// Return a new pending game, with the bots already added to it. The game is made
+    /// big enough to leave room for at least one websocket client, and for full teams. Its map is
+    /// picked from the map pool, if any.
+    fn new_pending_game(&mut self) -> PendingGame {
+        let mut pending_game = PendingGame::new();
+        let bots = &self.config.bots;
+        let mut size = cmp::max(pending_game.size as usize, bots.len() + 1);
+        if let Some(team_size) = self.config.team_size {
+            // Make room for at least two full teams.
+            size = cmp::max(size, 2 * team_size).div_ceil(team_size) * team_size;
+            pending_game.team_size = Some(team_size as u8);
+        }
+        pending_game.size = size as u8;
+        pending_game.resign_policy = self.config.resign_policy;
+        pending_game.capture_rule = self.config.capture_rule;
+        pending_game.generator = self.config.generator.clone();
+        if let Some(ref mut pool) = self.config.map_pool {
+            pending_game.map_file = pool.pick(size).cloned();
+            match pending_game.map_file {
+                Some(ref map_file) => info!("next game will be played on {:?}", map_file.name),
+                None => info!(
+                    "no map for {} players in the pool: using a random map",
+                    size
+                ),
+            }
+        }
+        for spec in bots {
+            match bot::spawn_proxy(spec, self.config.bot_budget) {
+                Ok(mut connection) => {
+                    info!("adding bot \"{}\" to pending game", spec);
+                    connection.max_queue_len = self.config.max_queue_len;
+                    connection.stale_window = self.config.stale_window;
+                    pending_game.add(connection);
+                }
+                Err(e) => error!("failed to start bot \"{}\": {}", spec, e),
+            }
+        }
+        pending_game
+    }
 }
 
 impl Future for Server {
@@ -75,28 +148,36 @@ impl Future for Server {
     type Error = String;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let Server {
-            ref mut connections,
-            ref mut pending_game,
-            ref handle,
-        } = *self;
         loop {
-            match connections
+            match self
+                .connections
                 .poll()
                 .map_err(|()| "Failed to get new connections")?
             {
-                Async::Ready(Some(connection)) => {
+                Async::Ready(Some(mut connection)) => {
                     info!("adding client to pending game");
-                    pending_game.add(connection);
-                    if !pending_game.is_ready() {
+                    connection.max_queue_len = self.config.max_queue_len;
+                    connection.stale_window = self.config.stale_window;
+                    self.pending_game.add(connection);
+                    if !self.pending_game.is_ready() {
                         continue;
                     }
                     info!("pending game is ready, starting the game");
-                    let mut ready = mem::replace(pending_game, PendingGame::new());
-                    let mut new_game = ActiveGame::from(&mut ready);
+                    let next_game = self.new_pending_game();
+                    let mut ready = mem::replace(&mut self.pending_game, next_game);
+                    let mut new_game = match ActiveGame::new(&mut ready) {
+                        Ok(new_game) => new_game,
+                        Err(e) => {
+                            error!("failed to start the game: {}", e);
+                            continue;
+                        }
+                    };
+                    if let Some(ref directory) = self.config.record {
+                        new_game.record(directory.clone());
+                    }
                     // FIXME: I'm not 100% we can do that here, before spawning thegame
                     new_game.start_send_updates();
-                    handle.spawn(new_game.map_err(|e| error!("{}", e)));
+                    self.handle.spawn(new_game.map_err(|e| error!("{}", e)));
                 }
                 Async::Ready(None) => return Err("Connection proxy sender dropped".into()),
                 Async::NotReady => return Ok(Async::NotReady),
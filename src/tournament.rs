@@ -0,0 +1,481 @@
+//! This module contains a tournament runner, to compare bots (or versions of a bot) over many
+//! headless games.
+//!
+//! Bots play one-on-one matches. A match between two bots is made of one game per seed and per
+//! seating: with the same seed, the map is the same, and the bots swap their spawn positions, so
+//! that neither of them is favoured by the map. The seeds are the same for all the matches.
+//!
+//! Pairings are either round-robin (every bot meets every other bot once), or Swiss: at each
+//! round, bots are paired with bots that have a similar score, and that they did not meet yet
+//! when possible. A bot that already met all the bots left to pair plays a rematch against the
+//! next one.
+//!
+//! A won game is worth one point, a game that reaches the maximum number of turns is a draw worth
+//! half a point, and a lost game is worth nothing. The report contains the score rate of each bot
+//! with its 95% confidence interval, the average length of its games, and a rating on the Elo
+//! scale, fitted on all the games with the Bradley-Terry model.
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use headless::{HeadlessError, HeadlessGame};
+
+/// How the bots are paired.
+#[derive(Copy, Clone, Debug)]
+pub enum Format {
+    /// Every bot plays against every other bot
+    RoundRobin,
+    /// Bots with similar scores play against each other, for the given number of rounds
+    Swiss { rounds: usize },
+}
+
+impl Format {
+    /// Return the format with the given name: `round-robin`, or `swiss` with the usual number of
+    /// rounds for the given number of bots.
+    pub fn from_name(name: &str, nb_bots: usize) -> Option<Self> {
+        match name {
+            "round-robin" => Some(Format::RoundRobin),
+            "swiss" => Some(Format::Swiss {
+                rounds: default_rounds(nb_bots),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Return the number of rounds needed by a Swiss tournament to separate the given number of
+/// bots, ie `ceil(log2(nb_bots))`.
+pub fn default_rounds(nb_bots: usize) -> usize {
+    let mut rounds = 0;
+    while (1 << rounds) < nb_bots {
+        rounds += 1;
+    }
+    rounds.max(1)
+}
+
+/// Tournament settings
+pub struct Config {
+    pub format: Format,
+    /// Number of seeds (ie of maps) each match is played on. Each seed is played once per seating.
+    pub seeds: usize,
+    /// The first seed. The next ones are `seed + 1`, `seed + 2`, etc, wrapping around after
+    /// `u64::MAX`.
+    pub seed: u64,
+    /// Time bots have to answer each update
+    pub budget: Duration,
+    /// Games that reach this number of turns are draws
+    pub max_turns: usize,
+    /// Number of games played in parallel
+    pub threads: usize,
+}
+
+/// A game to play: the bots (by index) for each seat, and the seed of the map.
+#[derive(Clone, Debug)]
+struct Job {
+    seats: Vec<usize>,
+    seed: u64,
+}
+
+/// The result of a game.
+#[derive(Clone, Debug)]
+pub struct GameResult {
+    /// The bot (by index) at each seat
+    pub seats: Vec<usize>,
+    /// Seed of the map
+    pub seed: u64,
+    /// The bot (by index) that won the game, if any
+    pub winner: Option<usize>,
+    /// Number of turns played
+    pub turns: usize,
+}
+
+impl GameResult {
+    /// Return the number of points the given bot scored in the game.
+    fn score(&self, bot: usize) -> f64 {
+        match self.winner {
+            Some(winner) if winner == bot => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        }
+    }
+}
+
+/// The results of a bot over the whole tournament.
+#[derive(Clone, Debug)]
+pub struct Standing {
+    pub bot: String,
+    pub games: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    /// Average number of turns of the bot's games
+    pub average_turns: f64,
+    /// Rating on the Elo scale. The average rating is 1500.
+    pub rating: f64,
+}
+
+impl Standing {
+    /// Return the number of points scored by the bot
+    pub fn score(&self) -> f64 {
+        self.wins as f64 + 0.5 * self.draws as f64
+    }
+
+    /// Return the share of the available points the bot scored.
+    pub fn score_rate(&self) -> f64 {
+        if self.games == 0 {
+            0.0
+        } else {
+            self.score() / self.games as f64
+        }
+    }
+
+    /// Return the 95% confidence interval of the score rate (Wilson score interval).
+    pub fn confidence_interval(&self) -> (f64, f64) {
+        if self.games == 0 {
+            return (0.0, 1.0);
+        }
+        let (n, p, z) = (self.games as f64, self.score_rate(), 1.96);
+        let denominator = 1.0 + z * z / n;
+        let center = (p + z * z / (2.0 * n)) / denominator;
+        let half_width = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / denominator;
+        (
+            (center - half_width).max(0.0),
+            (center + half_width).min(1.0),
+        )
+    }
+}
+
+pub struct Tournament {
+    bots: Vec<String>,
+    config: Config,
+    pub(crate) results: Vec<GameResult>,
+}
+
+impl Tournament {
+    /// Return a new tournament between the given bots (see `bot::from_spec`).
+    pub fn new(bots: Vec<String>, config: Config) -> Self {
+        Tournament {
+            bots,
+            config,
+            results: Vec::new(),
+        }
+    }
+
+    /// Play all the games of the tournament.
+    pub fn run(&mut self) -> Result<(), TournamentError> {
+        match self.config.format {
+            Format::RoundRobin => {
+                let mut pairings = Vec::new();
+                for i in 0..self.bots.len() {
+                    for j in (i + 1)..self.bots.len() {
+                        pairings.push((i, j));
+                    }
+                }
+                self.play(&pairings)
+            }
+            Format::Swiss { rounds } => {
+                let mut played = HashSet::new();
+                let mut byes = vec![0; self.bots.len()];
+                for round in 0..rounds {
+                    let pairings = self.swiss_pairings(&played, &mut byes);
+                    info!("swiss round {}: {:?}", round + 1, pairings);
+                    self.play(&pairings)?;
+                    played.extend(pairings);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Return the pairings of the next Swiss round: going down the ranking, each bot is paired
+    /// with the next bot it did not meet yet, or with the next bot if it met all of them already.
+    /// If the number of bots is odd, the lowest ranked bot among the ones that sat out the fewest
+    /// rounds does not play this round.
+    pub(crate) fn swiss_pairings(
+        &self,
+        played: &HashSet<(usize, usize)>,
+        byes: &mut [usize],
+    ) -> Vec<(usize, usize)> {
+        let standings = self.standings();
+        let mut ranking: Vec<usize> = (0..self.bots.len()).collect();
+        ranking.sort_by(|a, b| {
+            standings[*b]
+                .score()
+                .partial_cmp(&standings[*a].score())
+                .unwrap()
+                .then(a.cmp(b))
+        });
+
+        if ranking.len() % 2 == 1 {
+            let fewest = ranking.iter().map(|bot| byes[*bot]).min().unwrap_or(0);
+            let bye = ranking
+                .iter()
+                .rposition(|bot| byes[*bot] == fewest)
+                .unwrap();
+            byes[ranking.remove(bye)] += 1;
+        }
+
+        let mut pairings = Vec::new();
+        while ranking.len() >= 2 {
+            let first = ranking.remove(0);
+            let opponent = ranking
+                .iter()
+                .position(|other| {
+                    !played.contains(&(first, *other)) && !played.contains(&(*other, first))
+                })
+                .unwrap_or_else(|| {
+                    warn!("bot {} met all the bots left to pair, rematch", first);
+                    0
+                });
+            let second = ranking.remove(opponent);
+            pairings.push((first.min(second), first.max(second)));
+        }
+        pairings
+    }
+
+    /// Play the matches between the given pairs of bots.
+    fn play(&mut self, pairings: &[(usize, usize)]) -> Result<(), TournamentError> {
+        let mut jobs = Vec::new();
+        for (a, b) in pairings {
+            for i in 0..self.config.seeds {
+                let seed = self.config.seed.wrapping_add(i as u64);
+                jobs.push(Job {
+                    seats: vec![*a, *b],
+                    seed,
+                });
+                jobs.push(Job {
+                    seats: vec![*b, *a],
+                    seed,
+                });
+            }
+        }
+
+        let nb_jobs = jobs.len();
+        let nb_results = self.results.len();
+        let jobs = Arc::new(Mutex::new(jobs.into_iter()));
+        let (result_tx, result_rx) = channel();
+        for _ in 0..self.config.threads.max(1).min(nb_jobs) {
+            let jobs = Arc::clone(&jobs);
+            let result_tx = result_tx.clone();
+            let bots = self.bots.clone();
+            let (budget, max_turns) = (self.config.budget, self.config.max_turns);
+            thread::spawn(move || loop {
+                let job = match jobs.lock().unwrap().next() {
+                    Some(job) => job,
+                    None => break,
+                };
+                let specs: Vec<String> = job.seats.iter().map(|i| bots[*i].clone()).collect();
+                let result = HeadlessGame::with_seed(&specs, budget, max_turns, job.seed)
+                    .map(HeadlessGame::run)
+                    .map(|outcome| GameResult {
+                        winner: outcome.winner.map(|player| job.seats[player]),
+                        seats: job.seats,
+                        seed: job.seed,
+                        turns: outcome.turns,
+                    });
+                if result_tx.send(result).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(result_tx);
+
+        for result in result_rx.iter() {
+            let result = result?;
+            info!(
+                "game {}/{} on seed {}: bots {:?}, winner {:?} after {} turns",
+                self.results.len() - nb_results + 1,
+                nb_jobs,
+                result.seed,
+                result.seats,
+                result.winner,
+                result.turns
+            );
+            self.results.push(result);
+        }
+        // The results stop coming early if a worker thread panicked.
+        let received = self.results.len() - nb_results;
+        if received < nb_jobs {
+            return Err(TournamentError::MissingResults {
+                expected: nb_jobs,
+                received,
+            });
+        }
+        Ok(())
+    }
+
+    /// Return the standing of each bot, in the order the bots were given.
+    pub fn standings(&self) -> Vec<Standing> {
+        let ratings = self.ratings();
+        self.bots
+            .iter()
+            .enumerate()
+            .map(|(bot, spec)| {
+                let games: Vec<&GameResult> = self
+                    .results
+                    .iter()
+                    .filter(|result| result.seats.contains(&bot))
+                    .collect();
+                let count = |f: &dyn Fn(&GameResult) -> bool| games.iter().filter(|g| f(g)).count();
+                let total_turns: usize = games.iter().map(|game| game.turns).sum();
+                Standing {
+                    bot: spec.clone(),
+                    games: games.len(),
+                    wins: count(&|game| game.winner == Some(bot)),
+                    draws: count(&|game| game.winner.is_none()),
+                    losses: count(&|game| game.winner.is_some() && game.winner != Some(bot)),
+                    average_turns: if games.is_empty() {
+                        0.0
+                    } else {
+                        total_turns as f64 / games.len() as f64
+                    },
+                    rating: ratings[bot],
+                }
+            })
+            .collect()
+    }
+
+    /// Fit the Bradley-Terry model on all the games, and return the strength of each bot on the
+    /// Elo scale. Each bot gets a virtual draw against an average bot, so that the ratings are
+    /// finite even for bots that won or lost all their games.
+    pub(crate) fn ratings(&self) -> Vec<f64> {
+        let n = self.bots.len();
+        // scores[i][j]: points scored by i against j. games[i][j]: games between i and j.
+        let mut scores = vec![vec![0.0; n]; n];
+        let mut games = vec![vec![0.0; n]; n];
+        for result in &self.results {
+            for &i in &result.seats {
+                for &j in result.seats.iter().filter(|j| **j != i) {
+                    scores[i][j] += result.score(i);
+                    games[i][j] += 1.0;
+                }
+            }
+        }
+
+        let mut strengths = vec![1.0f64; n];
+        for _ in 0..1000 {
+            let mut next = Vec::with_capacity(n);
+            for i in 0..n {
+                let won: f64 = scores[i].iter().sum::<f64>() + 0.5;
+                let mut expected = 1.0 / (strengths[i] + 1.0);
+                for j in (0..n).filter(|j| *j != i) {
+                    expected += games[i][j] / (strengths[i] + strengths[j]);
+                }
+                next.push(won / expected);
+            }
+            // Normalize, so that the geometric mean of the strengths is 1.
+            let mean = (next.iter().map(|s| s.ln()).sum::<f64>() / n as f64).exp();
+            let next: Vec<f64> = next.iter().map(|s| s / mean).collect();
+            let delta = next
+                .iter()
+                .zip(&strengths)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f64::max);
+            strengths = next;
+            if delta < 1e-9 {
+                break;
+            }
+        }
+        strengths
+            .iter()
+            .map(|s| 1500.0 + 400.0 * s.log10())
+            .collect()
+    }
+}
+
+/// A table of the standings, sorted by rating.
+impl fmt::Display for Tournament {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut standings = self.standings();
+        standings.sort_by(|a, b| b.rating.partial_cmp(&a.rating).unwrap());
+        let width = standings
+            .iter()
+            .map(|s| s.bot.len())
+            .max()
+            .unwrap_or(0)
+            .max(3);
+        writeln!(
+            f,
+            "{:>4}  {:<width$}  {:>6}  {:>5}  {:>5}  {:>5}  {:>5}  {:>6}  {:>13}  {:>9}",
+            "rank",
+            "bot",
+            "rating",
+            "games",
+            "wins",
+            "draws",
+            "loss",
+            "score",
+            "95% CI",
+            "avg turns",
+            width = width
+        )?;
+        for (rank, s) in standings.iter().enumerate() {
+            let (low, high) = s.confidence_interval();
+            writeln!(
+                f,
+                "{:>4}  {:<width$}  {:>6.0}  {:>5}  {:>5}  {:>5}  {:>5}  {:>5.1}%  {:>5.1}%-{:>5.1}%  {:>9.1}",
+                rank + 1,
+                s.bot,
+                s.rating,
+                s.games,
+                s.wins,
+                s.draws,
+                s.losses,
+                100.0 * s.score_rate(),
+                100.0 * low,
+                100.0 * high,
+                s.average_turns,
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// An error that aborts a tournament.
+#[derive(Debug)]
+pub enum TournamentError {
+    /// A game could not be started.
+    Game(HeadlessError),
+    /// Some games did not report their result, because a worker thread panicked.
+    MissingResults { expected: usize, received: usize },
+}
+
+impl From<HeadlessError> for TournamentError {
+    fn from(err: HeadlessError) -> Self {
+        TournamentError::Game(err)
+    }
+}
+
+impl Error for TournamentError {
+    fn description(&self) -> &str {
+        match *self {
+            TournamentError::Game(ref e) => e.description(),
+            TournamentError::MissingResults { .. } => "some games did not finish",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            TournamentError::Game(ref e) => Some(e),
+            TournamentError::MissingResults { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for TournamentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TournamentError::Game(e) => write!(f, "{}", e),
+            TournamentError::MissingResults { expected, received } => write!(
+                f,
+                "Tournament error: only {} games out of {} finished",
+                received, expected
+            ),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use tournament::{Config, Format, GameResult, Standing, Tournament};
+
+/// Return a tournament between the given number of bots, with the given results. Each result is
+/// `(first bot, second bot, winner)`.
+fn get_tournament(nb_bots: usize, results: &[(usize, usize, Option<usize>)]) -> Tournament {
+    let config = Config {
+        format: Format::Swiss { rounds: 1 },
+        seeds: 1,
+        seed: 0,
+        budget: Duration::from_millis(10),
+        max_turns: 100,
+        threads: 1,
+    };
+    let bots = (0..nb_bots).map(|i| format!("bot{}", i)).collect();
+    let mut tournament = Tournament::new(bots, config);
+    tournament.results = results
+        .iter()
+        .map(|&(a, b, winner)| GameResult {
+            seats: vec![a, b],
+            seed: 0,
+            winner,
+            turns: 50,
+        })
+        .collect();
+    tournament
+}
+
+fn get_standing(games: usize, wins: usize, draws: usize) -> Standing {
+    Standing {
+        bot: "bot".to_string(),
+        games,
+        wins,
+        draws,
+        losses: games - wins - draws,
+        average_turns: 0.0,
+        rating: 1500.0,
+    }
+}
+
+fn assert_close(value: f64, expected: f64) {
+    assert!(
+        (value - expected).abs() < 1e-4,
+        "{} is not close to {}",
+        value,
+        expected
+    );
+}
+
+#[test]
+fn test_ratings_equal() {
+    let tournament = get_tournament(2, &[(0, 1, Some(0)), (1, 0, Some(1)), (0, 1, None)]);
+    for rating in tournament.ratings() {
+        assert_close(rating, 1500.0);
+    }
+}
+
+#[test]
+fn test_ratings() {
+    // Bot 0 scored 3 points out of 4 against bot 1. With the virtual draws against an average
+    // bot, the ratings are symmetric around 1500, and their difference is about 400 * log10(3).
+    let tournament = get_tournament(
+        2,
+        &[
+            (0, 1, Some(0)),
+            (1, 0, Some(0)),
+            (0, 1, Some(0)),
+            (1, 0, Some(1)),
+        ],
+    );
+    let ratings = tournament.ratings();
+    assert_close(ratings[0] + ratings[1], 3000.0);
+    assert!(ratings[0] > ratings[1]);
+    let difference = ratings[0] - ratings[1];
+    assert!(difference > 100.0 && difference < 400.0 * 3f64.log10());
+
+    // A bot that won all its games still has a finite rating.
+    let tournament = get_tournament(3, &[(0, 1, Some(0)), (0, 2, Some(0)), (1, 2, Some(1))]);
+    let ratings = tournament.ratings();
+    assert!(ratings.iter().all(|rating| rating.is_finite()));
+    assert!(ratings[0] > ratings[1] && ratings[1] > ratings[2]);
+    assert_close(ratings.iter().sum::<f64>(), 4500.0);
+}
+
+#[test]
+fn test_confidence_interval() {
+    let (low, high) = get_standing(0, 0, 0).confidence_interval();
+    assert_close(low, 0.0);
+    assert_close(high, 1.0);
+
+    let (low, high) = get_standing(10, 5, 0).confidence_interval();
+    assert_close(low, 0.23659);
+    assert_close(high, 0.76341);
+    // Draws are worth half a point
+    assert_eq!(get_standing(10, 0, 10).confidence_interval(), (low, high));
+
+    let (low, high) = get_standing(10, 10, 0).confidence_interval();
+    assert_close(low, 0.72246);
+    assert_close(high, 1.0);
+    let (low, high) = get_standing(10, 0, 0).confidence_interval();
+    assert_close(low, 0.0);
+    assert_close(high, 0.27754);
+}
+
+#[test]
+fn test_swiss_pairings() {
+    let tournament = get_tournament(4, &[]);
+    let mut byes = vec![0; 4];
+    let pairings = tournament.swiss_pairings(&HashSet::new(), &mut byes);
+    assert_eq!(pairings, vec![(0, 1), (2, 3)]);
+    assert_eq!(byes, vec![0; 4]);
+
+    // Bots do not meet twice
+    let played: HashSet<(usize, usize)> = pairings.into_iter().collect();
+    let pairings = tournament.swiss_pairings(&played, &mut byes);
+    assert_eq!(pairings, vec![(0, 2), (1, 3)]);
+
+    // Bots are paired according to their scores
+    let tournament = get_tournament(4, &[(0, 1, Some(1)), (2, 3, Some(3))]);
+    let pairings = tournament.swiss_pairings(&HashSet::new(), &mut byes);
+    assert_eq!(pairings, vec![(1, 3), (0, 2)]);
+
+    // When a bot met all the others already, it plays a rematch against the next bot.
+    let tournament = get_tournament(2, &[]);
+    let played: HashSet<(usize, usize)> = vec![(0, 1)].into_iter().collect();
+    let pairings = tournament.swiss_pairings(&played, &mut [0; 2]);
+    assert_eq!(pairings, vec![(0, 1)]);
+}
+
+#[test]
+fn test_swiss_byes() {
+    // The lowest ranked bot sits out, then the lowest ranked bot that did not sit out yet.
+    let tournament = get_tournament(3, &[]);
+    let mut byes = vec![0; 3];
+    let pairings = tournament.swiss_pairings(&HashSet::new(), &mut byes);
+    assert_eq!(pairings, vec![(0, 1)]);
+    assert_eq!(byes, vec![0, 0, 1]);
+
+    let played: HashSet<(usize, usize)> = pairings.into_iter().collect();
+    let pairings = tournament.swiss_pairings(&played, &mut byes);
+    assert_eq!(pairings, vec![(0, 2)]);
+    assert_eq!(byes, vec![0, 1, 1]);
+}
@@ -0,0 +1,283 @@
+//! This module contains a batch of independent environments, stepped in parallel.
+//!
+//! The environments are split between worker threads, each thread owning its environments (and
+//! thus their games) for its whole lifetime. Observations are always encoded as planes, and are
+//! stacked: the observations of a step have the shape `(envs, agents, planes, height, width)`.
+//!
+//! When a game is over, its environment is automatically reset with a new seed, and the
+//! observations returned for it are the first observations of the new episode. Each environment
+//! draws its successive seeds from its own random generator, seeded with the seed it got at the
+//! last explicit reset, so a batch always plays the same sequence of maps for a given seed.
+//!
+//! Like `Env`, a `VecEnv` can be driven over a JSON lines protocol (see `VecEnv::serve`):
+//!
+//! ```text
+//! >>> {"type":"reset","seed":42}
+//! <<< {"shape":[envs,agents,planes,height,width],"observations":"..."}
+//! >>> {"type":"step","actions":[[{"type":"move","from":42,"direction":"left"},null],[null,null]]}
+//! <<< {"shape":[...],"observations":"...","rewards":[[...],[...]],"dones":[[...],[...]]}
+//! ```
+//!
+//! where `observations` is the base64 encoding of the stacked little-endian `u16` planes. If an
+//! environment cannot be reset or stepped, the response is `{"error":"..."}`, like with `Env`.
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use serde_json;
+
+use core::{max_grid_size, Action, NB_PLANES};
+use env::{self, AgentObservation, Env, EnvError, ObservationKind, Rewards};
+
+/// The result of a step for a single environment.
+struct EnvStep {
+    /// The planes of all the agents
+    observation: Vec<u8>,
+    rewards: Vec<f64>,
+    dones: Vec<bool>,
+}
+
+enum Command {
+    /// Reset each environment of the worker with the given seed
+    Reset(Vec<u64>),
+    /// Step each environment of the worker with the given actions
+    Step(Vec<Vec<Option<Action>>>),
+}
+
+/// A thread owning some of the environments. The thread stops when the worker is dropped.
+struct Worker {
+    nb_envs: usize,
+    commands: Sender<Command>,
+    results: Receiver<Vec<Result<EnvStep, EnvError>>>,
+}
+
+impl Worker {
+    fn spawn(envs: Vec<Env>) -> Self {
+        let (command_tx, command_rx) = channel();
+        let (result_tx, result_rx) = channel();
+        let nb_envs = envs.len();
+        thread::spawn(move || {
+            let mut envs: Vec<(Env, StdRng)> = envs
+                .into_iter()
+                .map(|env| (env, StdRng::seed_from_u64(0)))
+                .collect();
+            for command in command_rx.iter() {
+                let results = match command {
+                    Command::Reset(seeds) => envs
+                        .iter_mut()
+                        .zip(seeds)
+                        .map(|((env, rng), seed)| {
+                            *rng = StdRng::seed_from_u64(seed);
+                            reset(env, seed)
+                        })
+                        .collect(),
+                    Command::Step(actions) => envs
+                        .iter_mut()
+                        .zip(actions)
+                        .map(|((env, rng), actions)| step(env, rng, &actions))
+                        .collect(),
+                };
+                if result_tx.send(results).is_err() {
+                    break;
+                }
+            }
+        });
+        Worker {
+            nb_envs,
+            commands: command_tx,
+            results: result_rx,
+        }
+    }
+}
+
+fn reset(env: &mut Env, seed: u64) -> Result<EnvStep, EnvError> {
+    let observations = env.reset(seed)?;
+    Ok(EnvStep {
+        rewards: vec![0.0; observations.len()],
+        dones: vec![false; observations.len()],
+        observation: stack(&observations),
+    })
+}
+
+/// Step the given environment, and reset it if the episode is over for all the agents.
+fn step(env: &mut Env, rng: &mut StdRng, actions: &[Option<Action>]) -> Result<EnvStep, EnvError> {
+    let step = env.step(actions)?;
+    let observation = if step.dones.iter().all(|done| *done) {
+        stack(&env.reset(rng.gen())?)
+    } else {
+        stack(&step.observations)
+    };
+    Ok(EnvStep {
+        observation,
+        rewards: step.rewards,
+        dones: step.dones,
+    })
+}
+
+fn stack(observations: &[AgentObservation]) -> Vec<u8> {
+    let mut stacked = Vec::new();
+    for observation in observations {
+        match observation {
+            AgentObservation::Planes(planes) => stacked.extend(planes.to_bytes()),
+            AgentObservation::Update(_) => unreachable!("observations are always planes"),
+        }
+    }
+    stacked
+}
+
+/// The stacked results of a step.
+#[derive(Serialize)]
+pub struct VecStep {
+    /// Shape of the observations: `(envs, agents, planes, height, width)`
+    pub shape: (usize, usize, usize, usize, usize),
+    /// Stacked observations, as little-endian `u16`
+    #[serde(serialize_with = "serialize_bytes")]
+    pub observations: Vec<u8>,
+    /// The rewards of each agent in each environment
+    pub rewards: Vec<Vec<f64>>,
+    /// Whether the episode is over for each agent in each environment. When all the agents of an
+    /// environment are done, the environment has been reset.
+    pub dones: Vec<Vec<bool>>,
+}
+
+pub struct VecEnv {
+    nb_envs: usize,
+    nb_agents: usize,
+    workers: Vec<Worker>,
+    /// Whether the environments have been reset at least once
+    started: bool,
+}
+
+impl VecEnv {
+    /// Return `nb_envs` environments for `nb_agents` agents each, split between `nb_threads`
+    /// threads.
+    pub fn new(
+        nb_envs: usize,
+        nb_agents: usize,
+        nb_threads: usize,
+        max_turns: usize,
+        rewards: Rewards,
+    ) -> Self {
+        let nb_threads = nb_threads.max(1).min(nb_envs.max(1));
+        let workers = (0..nb_threads)
+            .map(|thread| {
+                // Spread the environments as evenly as possible
+                let nb = nb_envs / nb_threads + (thread < nb_envs % nb_threads) as usize;
+                let envs = (0..nb)
+                    .map(|_| {
+                        let mut env = Env::new(nb_agents, max_turns, rewards);
+                        env.set_observation_kind(ObservationKind::Planes);
+                        env
+                    })
+                    .collect();
+                Worker::spawn(envs)
+            })
+            .collect();
+        VecEnv {
+            nb_envs,
+            nb_agents,
+            workers,
+            started: false,
+        }
+    }
+
+    /// Reset all the environments. The environments get the seeds `seed`, `seed + 1`, etc.
+    /// Return an error if one of the environments cannot be reset.
+    pub fn reset(&mut self, seed: u64) -> Result<VecStep, EnvError> {
+        let mut seeds = (0..self.nb_envs as u64).map(|i| seed.wrapping_add(i));
+        for worker in &self.workers {
+            let seeds = seeds.by_ref().take(worker.nb_envs).collect();
+            worker
+                .commands
+                .send(Command::Reset(seeds))
+                .expect("environment worker died");
+        }
+        self.started = true;
+        self.collect()
+    }
+
+    /// Step all the environments. `actions` must contain the actions of all the agents, for
+    /// each environment. Return an error if one of the environments cannot be stepped, or reset
+    /// at the end of its episode.
+    pub fn step(&mut self, actions: Vec<Vec<Option<Action>>>) -> Result<VecStep, EnvError> {
+        if !self.started {
+            return Err(EnvError::NotStarted);
+        }
+        if actions.len() != self.nb_envs {
+            return Err(EnvError::InvalidActions(actions.len()));
+        }
+        if let Some(invalid) = actions.iter().find(|a| a.len() != self.nb_agents) {
+            return Err(EnvError::InvalidActions(invalid.len()));
+        }
+        let mut actions = actions.into_iter();
+        for worker in &self.workers {
+            let actions = actions.by_ref().take(worker.nb_envs).collect();
+            worker
+                .commands
+                .send(Command::Step(actions))
+                .expect("environment worker died");
+        }
+        self.collect()
+    }
+
+    /// Wait for all the workers to be done, and stack their results. If an environment failed,
+    /// return its error, once all the workers are done.
+    fn collect(&mut self) -> Result<VecStep, EnvError> {
+        let size = max_grid_size(self.nb_agents);
+        let mut result = VecStep {
+            shape: (self.nb_envs, self.nb_agents, NB_PLANES, size, size),
+            observations: Vec::with_capacity(
+                self.nb_envs * self.nb_agents * NB_PLANES * size * size * 2,
+            ),
+            rewards: Vec::with_capacity(self.nb_envs),
+            dones: Vec::with_capacity(self.nb_envs),
+        };
+        let mut error = None;
+        for worker in &self.workers {
+            for step in worker.results.recv().expect("environment worker died") {
+                match step {
+                    Ok(step) => {
+                        result.observations.extend(step.observation);
+                        result.rewards.push(step.rewards);
+                        result.dones.push(step.dones);
+                    }
+                    Err(e) => error = error.or(Some(e)),
+                }
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(result),
+        }
+    }
+
+    /// Serve the environments over the JSON lines protocol described in the module
+    /// documentation, until the input is closed.
+    pub fn serve<R: BufRead, W: Write>(&mut self, input: R, output: W) -> io::Result<()> {
+        env::serve(input, output, |request| {
+            let step = match request {
+                Request::Reset { seed } => {
+                    self.reset(seed.unwrap_or_else(|| thread_rng().gen()))?
+                }
+                Request::Step { actions } => self.step(actions)?,
+            };
+            Ok(serde_json::to_value(step)?)
+        })
+    }
+}
+
+fn serialize_bytes<S: ::serde::Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&env::base64(bytes))
+}
+
+/// A request of the JSON lines protocol.
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "lowercase")]
+enum Request {
+    /// Reset all the environments. If no seed is given, a random one is used.
+    Reset { seed: Option<u64> },
+    /// Step all the environments
+    Step { actions: Vec<Vec<Option<Action>>> },
+}
@@ -0,0 +1,83 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use env::{AgentObservation, Env, EnvError, ObservationKind, Rewards};
+use vec_env::VecEnv;
+
+/// Return the stacked planes a single environment returns after a reset with the given seed.
+fn reset_planes(seed: u64, max_turns: usize) -> Vec<u8> {
+    let mut env = Env::new(2, max_turns, Rewards::default());
+    env.set_observation_kind(ObservationKind::Planes);
+    let mut planes = Vec::new();
+    for observation in env.reset(seed).unwrap() {
+        match observation {
+            AgentObservation::Planes(observation) => planes.extend(observation.to_bytes()),
+            AgentObservation::Update(_) => panic!("unexpected update"),
+        }
+    }
+    planes
+}
+
+#[test]
+fn test_stacking() {
+    // 5 environments on 2 threads: the first thread has 3 of them, the second one 2.
+    let mut envs = VecEnv::new(5, 2, 2, 10, Rewards::default());
+    let step = envs.reset(7).unwrap();
+    let (nb_envs, nb_agents, planes, height, width) = step.shape;
+    assert_eq!((nb_envs, nb_agents), (5, 2));
+    let size = nb_agents * planes * height * width * 2;
+    assert_eq!(step.observations.len(), nb_envs * size);
+    for i in 0..nb_envs {
+        let observation = &step.observations[i * size..(i + 1) * size];
+        assert_eq!(observation, &reset_planes(7 + i as u64, 10)[..]);
+    }
+    assert_eq!(step.rewards, vec![vec![0.0, 0.0]; 5]);
+    assert_eq!(step.dones, vec![vec![false, false]; 5]);
+
+    let step = envs.step(vec![vec![None, None]; 5]).unwrap();
+    assert_eq!(step.observations.len(), nb_envs * size);
+    assert_eq!(step.rewards.len(), 5);
+    assert_eq!(step.dones, vec![vec![false, false]; 5]);
+}
+
+#[test]
+fn test_auto_reset() {
+    let mut envs = VecEnv::new(3, 2, 2, 2, Rewards::default());
+    let _ = envs.reset(7).unwrap();
+    let step = envs.step(vec![vec![None, None]; 3]).unwrap();
+    assert_eq!(step.dones, vec![vec![false, false]; 3]);
+    let step = envs.step(vec![vec![None, None]; 3]).unwrap();
+    assert_eq!(step.dones, vec![vec![true, true]; 3]);
+
+    // The observations are the first ones of the new episode, whose seed is drawn from the
+    // environment's generator.
+    let size = step.observations.len() / 3;
+    for i in 0..3 {
+        let seed = StdRng::seed_from_u64(7 + i as u64).gen();
+        let observation = &step.observations[i * size..(i + 1) * size];
+        assert_eq!(observation, &reset_planes(seed, 2)[..]);
+    }
+
+    // The new episodes go on.
+    let step = envs.step(vec![vec![None, None]; 3]).unwrap();
+    assert_eq!(step.dones, vec![vec![false, false]; 3]);
+}
+
+#[test]
+fn test_invalid_actions() {
+    let mut envs = VecEnv::new(2, 2, 2, 10, Rewards::default());
+    match envs.step(vec![vec![None, None]; 2]) {
+        Err(EnvError::NotStarted) => {}
+        _ => panic!("the environments have not been reset"),
+    }
+    let _ = envs.reset(7).unwrap();
+    match envs.step(vec![vec![None, None]; 3]) {
+        Err(EnvError::InvalidActions(3)) => {}
+        _ => panic!("there are only 2 environments"),
+    }
+    match envs.step(vec![vec![None, None], vec![None]]) {
+        Err(EnvError::InvalidActions(1)) => {}
+        _ => panic!("there are 2 agents per environment"),
+    }
+    // The environments can still be stepped.
+    assert!(envs.step(vec![vec![None, None]; 2]).is_ok());
+}
@@ -8,6 +8,7 @@
  * CellType.Open     // represent an empty cell
  * CellType.City     // represent a cell containing a city
  * CellType.General  // represent a cell containing a general
+ * CellType.Void     // represent a cell that is out of the map
  * </pre>
  */
 let CellType = Object.freeze({
@@ -15,6 +16,7 @@ let CellType = Object.freeze({
   Open: 2,
   City: 3,
   General: 4,
+  Void: 5,
 
   /**
    * Return the cell type as a string.
@@ -28,6 +30,7 @@ let CellType = Object.freeze({
    * CellType.toString(CellType.Open)     // "open"
    * CellType.toString(CellType.City)     // "city"
    * CellType.toString(CellType.General)  // "general"
+   * CellType.toString(CellType.Void)     // "void"
    */
   toString: function(value) {
     if (!Number.isInteger(value)) {
@@ -42,6 +45,8 @@ let CellType = Object.freeze({
         return "city";
       case CellType.General:
         return "general";
+      case CellType.Void:
+        return "void";
       default:
         throw RangeError("Not a valid CellType: " + value);
     }
@@ -63,6 +68,7 @@ let CellType = Object.freeze({
    * CellType.fromString("open")      // CellType.Open
    * CellType.fromString("city")      // CellType.City
    * CellType.fromString("general")   // CellType.General
+   * CellType.fromString("void")      // CellType.Void
    * // This method is case sensitive, so this throws an error:
    * CellType.fromString("General")
    */
@@ -76,6 +82,8 @@ let CellType = Object.freeze({
         return CellType.Open;
       case "mountain":
         return CellType.Mountain;
+      case "void":
+        return CellType.Void;
       default:
         throw "Not a valid CellType: " + string;
     }
@@ -48,6 +48,8 @@ class Grid {
     }
     this.table = table;
     this.selected = null;
+    // Whether moving off an edge reappears on the opposite edge
+    this.wraps = false;
   }
 
   /**
@@ -288,6 +290,10 @@ class Grid {
    * console.assert(grid.getNeighborCell(4, Direction.Down).index() === 7);
    * console.assert(grid.getNeighborCell(0, Direction.Left) === null);
    *
+   * // On a grid that wraps around its edges
+   * grid.wraps = true;
+   * console.assert(grid.getNeighborCell(0, Direction.Left).index() === 2);
+   *
    */
   getNeighborCell(index, direction) {
     if (!this.isValidIndex(index)) {
@@ -297,22 +303,30 @@ class Grid {
     switch (direction) {
       case Direction.Right:
         if (coord.col === this.width() - 1) {
-          return null;
+          return this.wraps
+            ? this.getCellSafe(index + 1 - this.width())
+            : null;
         }
         return this.getCellSafe(index + 1);
       case Direction.Left:
         if (coord.col === 0) {
-          return null;
+          return this.wraps
+            ? this.getCellSafe(index - 1 + this.width())
+            : null;
         }
         return this.getCellSafe(index - 1);
       case Direction.Up:
         if (coord.row === 0) {
-          return null;
+          return this.wraps
+            ? this.getCellSafe(index - this.width() + this.length())
+            : null;
         }
         return this.getCellSafe(index - this.width());
       case Direction.Down:
         if (coord.row === this.height() - 1) {
-          return null;
+          return this.wraps
+            ? this.getCellSafe(index + this.width() - this.length())
+            : null;
         }
         return this.getCellSafe(index + this.width());
       default:
@@ -90,7 +90,7 @@ class InputEventsHandler {
     if (!target) {
       return;
     }
-    if (target.type == CellType.Mountain) {
+    if (target.type == CellType.Mountain || target.type == CellType.Void) {
       return;
     }
     let action = {
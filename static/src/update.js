@@ -56,6 +56,7 @@ function updateGrid(grid, update) {
 
   if (grid.length() === 0) {
     grid.init(update.height, update.width);
+    grid.wraps = update.topology === "torus";
   }
 
   for (let i = 0; i < update.tiles.length; i++) {
@@ -63,6 +64,12 @@ function updateGrid(grid, update) {
     let cell = grid.getCell(index);
     updateCell(cell, tile);
   }
+  // The cells that are out of the map are only listed in the first update
+  if (update.void !== undefined) {
+    for (let index of update.void) {
+      grid.getCell(index).type = CellType.Void;
+    }
+  }
 }
 
 /**
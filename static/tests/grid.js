@@ -116,3 +116,12 @@ QUnit.test("getNeighborCell", function(assert) {
   assert.equal(grid.getNeighborCell(42, Direction.Down), null, "null invalid");
   assert.equal(grid.getNeighborCell(-1, Direction.Down), null, "null invalid");
 });
+
+QUnit.test("getNeighborCell wraps", function(assert) {
+  let grid = this.grid;
+  grid.wraps = true;
+  assert.equal(grid.getNeighborCell(1, Direction.Up).index(), 9, "wrap up");
+  assert.equal(grid.getNeighborCell(4, Direction.Left).index(), 7, "wrap left");
+  assert.equal(grid.getNeighborCell(3, Direction.Right).index(), 0, "wrap right");
+  assert.equal(grid.getNeighborCell(10, Direction.Down).index(), 2, "wrap down");
+});
@@ -10,6 +10,7 @@ QUnit.test("fromString", function(assert) {
   assert.equal(CellType.fromString("open"), CellType.Open);
   assert.equal(CellType.fromString("city"), CellType.City);
   assert.equal(CellType.fromString("general"), CellType.General);
+  assert.equal(CellType.fromString("void"), CellType.Void);
 });
 
 QUnit.test("toString", function(assert) {
@@ -17,6 +18,7 @@ QUnit.test("toString", function(assert) {
   assert.equal(CellType.toString(CellType.Open), "open");
   assert.equal(CellType.toString(CellType.City), "city");
   assert.equal(CellType.toString(CellType.General), "general");
+  assert.equal(CellType.toString(CellType.Void), "void");
 });
 
 QUnit.module("Direction", {});